@@ -0,0 +1,207 @@
+use crate::error::Error;
+use crate::iflow::config::SettingsBackup;
+use crate::logging;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use tokio::sync::Notify;
+
+/// Signal that cancelled the run, e.g. "SIGTERM"
+static SIGNAL: OnceLock<&'static str> = OnceLock::new();
+
+/// Wakes everything waiting in [`cancelled`]
+static CANCELLED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Whether an agent session is running and will stop itself on cancellation
+static SESSION_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Settings file to put back when the run is cancelled
+static SETTINGS_BACKUP: Mutex<Option<SettingsBackup>> = Mutex::new(None);
+
+/// Whether a SIGTERM or SIGINT was received
+pub fn is_cancelled() -> bool {
+    SIGNAL.get().is_some()
+}
+
+/// The cancellation error, once a signal was received
+pub fn error() -> Option<Error> {
+    SIGNAL
+        .get()
+        .map(|signal| Error::Cancelled(format!("received {}", signal)))
+}
+
+/// Completes once a SIGTERM or SIGINT was received
+pub async fn cancelled() {
+    loop {
+        let notified = CANCELLED.notified();
+        if is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Marks an agent session as running until the guard is dropped. While a
+/// session runs, the first signal only cancels it; the session disconnects
+/// and reports the cancellation itself.
+pub fn session() -> SessionGuard {
+    SESSION_ACTIVE.store(true, Ordering::SeqCst);
+    SessionGuard
+}
+
+/// Guard returned by [`session`]
+#[derive(Debug)]
+pub struct SessionGuard;
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        SESSION_ACTIVE.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Registers the settings file to restore on cancellation
+pub fn restore_settings_on_cancel(backup: SettingsBackup) {
+    if let Ok(mut guard) = SETTINGS_BACKUP.lock() {
+        *guard = Some(backup);
+    }
+}
+
+/// Listens for SIGTERM and SIGINT. The first signal cancels the running
+/// session; `abort` is called with the cancellation error when no session is
+/// running or when a second signal arrives.
+pub fn listen<F>(abort: F)
+where
+    F: Fn(Error) + Send + 'static,
+{
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok();
+
+        loop {
+            #[cfg(unix)]
+            let signal = tokio::select! {
+                Ok(()) = tokio::signal::ctrl_c() => "SIGINT",
+                Some(_) = async { terminate.as_mut()?.recv().await } => "SIGTERM",
+                else => return,
+            };
+            #[cfg(not(unix))]
+            let signal = match tokio::signal::ctrl_c().await {
+                Ok(()) => "SIGINT",
+                Err(_) => return,
+            };
+
+            let first = SIGNAL.set(signal).is_ok();
+            if first && SESSION_ACTIVE.load(Ordering::SeqCst) {
+                logging::warn(&format!(
+                    "🛑 Received {}, cancelling the session...",
+                    signal
+                ));
+                CANCELLED.notify_waiters();
+                continue;
+            }
+
+            logging::warn(&format!("🛑 Received {}, aborting", signal));
+            CANCELLED.notify_waiters();
+            abort(error().unwrap_or_else(|| Error::Cancelled(format!("received {}", signal))));
+        }
+    });
+}
+
+/// Cleans up after a cancelled run: restores the settings file and terminates
+/// processes started by the action that are still running, such as an
+/// auto-started iFlow CLI or a pre-command
+pub fn cleanup() {
+    let backup = SETTINGS_BACKUP
+        .lock()
+        .ok()
+        .and_then(|mut guard| guard.take());
+    if let Some(backup) = backup {
+        match backup.restore() {
+            Ok(()) => logging::info("♻️  Restored the iFlow settings file"),
+            Err(e) => logging::warn(&format!("⚠️  Warning: {}", e)),
+        }
+    }
+
+    terminate_children();
+}
+
+/// Terminates every process started by the action that is still running,
+/// e.g. an iFlow CLI or a pre-command service
+pub fn terminate_children() {
+    terminate(&descendants(&process_tree(), std::process::id()));
+}
+
+/// Process IDs of the iFlow CLI auto-started by the SDK, i.e. the children of
+/// this process running `iflow --experimental-acp`, and of every process it
+/// started. Other children, such as pre-command services, are left out.
+pub fn iflow_process_tree() -> Vec<u32> {
+    let tree = process_tree();
+    let mut found = Vec::new();
+    for &child in tree.get(&std::process::id()).into_iter().flatten() {
+        let cmdline = std::fs::read(format!("/proc/{}/cmdline", child)).unwrap_or_default();
+        if cmdline
+            .split(|&b| b == 0)
+            .any(|arg| arg == b"--experimental-acp")
+        {
+            found.push(child);
+            found.extend(descendants(&tree, child));
+        }
+    }
+    found
+}
+
+/// Sends SIGTERM to the given processes that are still running
+pub fn terminate(pids: &[u32]) {
+    if !pids.is_empty() {
+        logging::info(&format!(
+            "🧹 Terminating {} remaining child process(es)",
+            pids.len()
+        ));
+        let _ = std::process::Command::new("kill")
+            .arg("-TERM")
+            .args(pids.iter().map(u32::to_string))
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+/// Process IDs of every descendant of `pid` in `tree`
+fn descendants(tree: &HashMap<u32, Vec<u32>>, pid: u32) -> Vec<u32> {
+    let mut found = Vec::new();
+    let mut pending = vec![pid];
+    while let Some(parent) = pending.pop() {
+        for &child in tree.get(&parent).into_iter().flatten() {
+            found.push(child);
+            pending.push(child);
+        }
+    }
+    found
+}
+
+/// Children of every running process, read from `/proc`; empty where `/proc`
+/// is not available
+fn process_tree() -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Some(child) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        // The parent PID is the second field after the parenthesized command name
+        let Some(parent) = std::fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|stat| {
+                stat.rsplit_once(')')?
+                    .1
+                    .split_whitespace()
+                    .nth(1)?
+                    .parse()
+                    .ok()
+            })
+        else {
+            continue;
+        };
+        children.entry(parent).or_default().push(child);
+    }
+    children
+}
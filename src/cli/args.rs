@@ -1,7 +1,19 @@
+use crate::github::outputs::SummaryTarget;
+use crate::github::review::ReviewMode;
+use crate::iflow::budget::SessionBudget;
+use crate::iflow::config::{AuthType, IFlowConfig};
+use crate::iflow::metrics::{ModelPrice, parse_model_prices};
+use crate::iflow::retry::{ErrorClass, RetryPolicy};
+use crate::iflow::stop_reason::StopReason;
+use crate::iflow::structured::OutputSchema;
+use crate::iflow::watchdog::WatchdogSettings;
+use crate::logging::LogFormat;
 use clap::Parser;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 /// iFlow CLI Action Command Line Interface
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[clap(
     name = "iflow-cli-action",
     version = "2.0.0",
@@ -10,62 +22,417 @@ use clap::Parser;
 
 This tool can run in two modes:
 1. GitHub Actions mode: Uses environment variables (INPUT_*) for configuration
-2. CLI mode: Uses command-line flags for configuration"#
+2. CLI mode: Uses command-line flags for configuration; `run` runs the agent
+   locally and prints the summary"#
 )]
 pub struct CliArgs {
-    /// The prompt to send to iFlow CLI (required in CLI mode)
-    #[clap(short, long, env = "INPUT_PROMPT")]
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// The prompt to send to iFlow CLI (required in CLI mode; optional
+    /// triage guidelines in triage mode)
+    #[clap(short, long, global = true, env = "INPUT_PROMPT")]
     pub prompt: Option<String>,
 
     /// API key for iFlow authentication
-    #[clap(long, env = "INPUT_API_KEY")]
+    #[clap(long, global = true, env = "INPUT_API_KEY")]
     pub api_key: Option<String>,
 
+    /// API key for the web search service (defaults to api_key for iflow auth)
+    #[clap(long, global = true, env = "INPUT_SEARCH_API_KEY")]
+    pub search_api_key: Option<String>,
+
+    /// Authentication type (iflow or openai-compatible)
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_AUTH_TYPE",
+        value_enum,
+        default_value = "iflow"
+    )]
+    pub auth_type: AuthType,
+
+    /// Visual theme written to the generated settings
+    #[clap(long, global = true, env = "INPUT_THEME", default_value = "Default")]
+    pub theme: String,
+
     /// Enable debug logging
-    #[clap(long, env = "INPUT_DEBUG")]
+    #[clap(long, global = true, env = "INPUT_DEBUG")]
     pub debug: bool,
 
     /// Complete settings JSON configuration
-    #[clap(long, env = "INPUT_SETTINGS_JSON")]
+    #[clap(long, global = true, env = "INPUT_SETTINGS_JSON")]
     pub settings_json: Option<String>,
 
+    /// YAML or TOML file with default inputs and named profiles, e.g.
+    /// .github/iflow.yml; inputs given explicitly take precedence
+    #[clap(long, global = true, env = "INPUT_CONFIG_FILE")]
+    pub config_file: Option<PathBuf>,
+
+    /// Profile of the config file to apply on top of its defaults
+    #[clap(long, global = true, env = "INPUT_PROFILE")]
+    pub profile: Option<String>,
+
     /// Base URL for the iFlow API
     #[clap(
         long,
+        global = true,
         env = "INPUT_BASE_URL",
         default_value = "https://apis.iflow.cn/v1"
     )]
     pub base_url: String,
 
     /// Model name to use
-    #[clap(long, env = "INPUT_MODEL", default_value = "qwen3-coder-plus")]
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_MODEL",
+        default_value = "qwen3-coder-plus"
+    )]
     pub model: String,
 
+    /// Models to try in order when the primary model fails (comma or newline separated)
+    #[clap(long, global = true, env = "INPUT_FALLBACK_MODELS")]
+    pub fallback_models: Option<String>,
+
+    /// Model error codes that trigger a fallback (comma separated)
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_FALLBACK_ERROR_CODES",
+        value_delimiter = ',',
+        default_value = "429,500,502,503,504,529"
+    )]
+    pub fallback_error_codes: Vec<i32>,
+
+    /// Number of retries for transient connection and API failures
+    #[clap(long, global = true, env = "INPUT_MAX_RETRIES", default_value = "2")]
+    pub max_retries: u32,
+
+    /// Delay in seconds before the first retry, doubled for each further retry
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_RETRY_INITIAL_DELAY",
+        default_value = "2"
+    )]
+    pub retry_initial_delay: f64,
+
+    /// Maximum delay in seconds between retries
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_RETRY_MAX_DELAY",
+        default_value = "30"
+    )]
+    pub retry_max_delay: f64,
+
+    /// Randomize retry delays to avoid synchronized retries
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_RETRY_JITTER",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    pub retry_jitter: bool,
+
+    /// Error classes to retry (connection, rate-limit, server-error, timeout; comma separated)
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_RETRY_ON",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "connection,rate-limit,server-error"
+    )]
+    pub retry_on: Vec<ErrorClass>,
+
     /// Working directory for execution
-    #[clap(long, env = "INPUT_WORKING_DIRECTORY", default_value = ".")]
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_WORKING_DIRECTORY",
+        default_value = "."
+    )]
     pub working_directory: String,
 
     /// Timeout in seconds (1-86400)
-    #[clap(long, env = "INPUT_TIMEOUT", default_value = "3600")]
+    #[clap(long, global = true, env = "INPUT_TIMEOUT", default_value = "3600")]
     pub timeout: u32,
 
+    /// Seconds without any message from iFlow before the session is treated
+    /// as stalled; disabled when unset
+    #[clap(long, global = true, env = "INPUT_IDLE_TIMEOUT")]
+    pub idle_timeout: Option<u32>,
+
+    /// Follow-up prompt that makes a stalled session count as retryable; the
+    /// retry receives the prompt followed by this one. Without it a stalled
+    /// session fails
+    #[clap(long, global = true, env = "INPUT_IDLE_RETRY_PROMPT")]
+    pub idle_retry_prompt: Option<String>,
+
+    /// Seconds between progress heartbeats while waiting for iFlow (0 disables them)
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_HEARTBEAT_INTERVAL",
+        default_value = "60"
+    )]
+    pub heartbeat_interval: u32,
+
     /// Shell command(s) to execute before running iFlow CLI
-    #[clap(long, env = "INPUT_PRECMD")]
+    #[clap(long, global = true, env = "INPUT_PRECMD")]
     pub precmd: Option<String>,
 
-    /// Version of GitHub CLI to install
-    #[clap(long, env = "INPUT_GH_VERSION")]
+    /// Version of GitHub CLI to install (exact, semver range or "latest")
+    #[clap(long, global = true, env = "INPUT_GH_VERSION")]
     pub gh_version: Option<String>,
 
-    /// Version of iFlow CLI to install
-    #[clap(long, env = "INPUT_IFLOW_VERSION")]
+    /// Version of iFlow CLI to install (exact, semver range or "latest")
+    #[clap(long, global = true, env = "INPUT_IFLOW_VERSION")]
     pub iflow_version: Option<String>,
 
+    /// Cache directory for downloaded GitHub CLI and iFlow CLI releases
+    #[clap(long, global = true, env = "INPUT_CACHE_DIR")]
+    pub cache_dir: Option<String>,
+
+    /// npm registry or mirror used to resolve and install iFlow CLI versions
+    #[clap(long, global = true, env = "INPUT_NPM_REGISTRY")]
+    pub npm_registry: Option<String>,
+
+    /// GitHub API URL (or mirror) used to resolve GitHub CLI versions
+    #[clap(long, global = true, env = "INPUT_GH_API_URL")]
+    pub gh_api_url: Option<String>,
+
+    /// Local GitHub CLI release tarball to install without network access
+    #[clap(long, global = true, env = "INPUT_GH_TARBALL")]
+    pub gh_tarball: Option<String>,
+
+    /// Local iFlow CLI package tarball (from `npm pack`) to install
+    #[clap(long, global = true, env = "INPUT_IFLOW_TARBALL")]
+    pub iflow_tarball: Option<String>,
+
+    /// Pre-populated npm cache directory used to install iFlow CLI offline
+    #[clap(long, global = true, env = "INPUT_NPM_CACHE")]
+    pub npm_cache: Option<String>,
+
+    /// Maximum number of agent turns; also written to `maxSessionTurns`
+    #[clap(long, global = true, env = "INPUT_MAX_TURNS")]
+    pub max_turns: Option<u32>,
+
+    /// Maximum number of tool calls before the session is cancelled
+    #[clap(long, global = true, env = "INPUT_MAX_TOOL_CALLS")]
+    pub max_tool_calls: Option<u32>,
+
+    /// Maximum number of output tokens before the session is cancelled
+    #[clap(long, global = true, env = "INPUT_MAX_TOKENS")]
+    pub max_tokens: Option<u64>,
+
+    /// Stop reasons that fail the step (max-tokens, max-turn-requests, refusal,
+    /// cancelled; comma separated). Not supported yet: iFlow does not report the
+    /// stop reason over WebSocket, so any value is rejected
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_FAIL_ON_STOP_REASON",
+        value_enum,
+        value_delimiter = ','
+    )]
+    pub fail_on_stop_reason: Vec<StopReason>,
+
+    /// JSON Schema (inline or a file path) the final answer must match; the
+    /// validated answer is written to the structured_result output
+    #[clap(long, global = true, env = "INPUT_OUTPUT_SCHEMA")]
+    pub output_schema: Option<String>,
+
+    /// Number of correction prompts when the answer does not match output_schema
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_OUTPUT_SCHEMA_RETRIES",
+        default_value = "2"
+    )]
+    pub output_schema_retries: u32,
+
+    /// Triage an issue natively: choose labels from the repository's labels and apply them
+    #[clap(long, global = true, env = "INPUT_TRIAGE")]
+    pub triage: bool,
+
+    /// Issue to triage; defaults to the issue of the workflow event
+    #[clap(long, global = true, env = "INPUT_ISSUE_NUMBER")]
+    pub issue_number: Option<u64>,
+
+    /// Publish review findings reported by the agent: off, annotations or pr-review
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_REVIEW_MODE",
+        value_enum,
+        default_value = "off"
+    )]
+    pub review_mode: ReviewMode,
+
+    /// Token used to create pull request reviews in pr-review mode
+    #[clap(long, global = true, env = "INPUT_GITHUB_TOKEN")]
+    pub github_token: Option<String>,
+
+    /// Console log format: pretty, plain (no emoji) or json (one object per line)
+    #[clap(
+        long,
+        global = true,
+        env = "INPUT_LOG_FORMAT",
+        value_enum,
+        default_value = "pretty"
+    )]
+    pub log_format: LogFormat,
+
+    /// OTLP/HTTP collector endpoint that receives traces of the run
+    #[clap(long, global = true, env = "INPUT_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Per-model prices in USD per million tokens, as JSON, used to estimate cost
+    #[clap(long, global = true, env = "INPUT_MODEL_PRICES")]
+    pub model_prices: Option<String>,
+
     /// Path to the settings file (for testing purposes)
-    #[clap(long, env = "SETTINGS_FILE_PATH")]
+    #[clap(long, global = true, env = "SETTINGS_FILE_PATH")]
     pub settings_file_path: Option<String>,
 
     /// Dry run mode for E2E testing (skips actual execution)
-    #[clap(long, env = "INPUT_DRY_RUN")]
+    #[clap(long, global = true, env = "INPUT_DRY_RUN")]
     pub dry_run: bool,
 }
+
+/// Subcommands; without one the action runs as in a workflow
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run the agent locally and write the summary to stdout or a file
+    Run(RunArgs),
+    /// Check the inputs and the settings they produce without running anything
+    Validate,
+    /// Print the effective iFlow settings.json with secrets redacted
+    PrintSettings,
+    /// Check the iFlow CLI, GitHub CLI and Node.js installs, the port iFlow
+    /// listens on and whether base_url is reachable
+    Doctor,
+    /// Render a summary from a saved log of a run
+    Summarize(SummarizeArgs),
+}
+
+/// Options of the `run` subcommand
+#[derive(clap::Args, Debug, Clone)]
+pub struct RunArgs {
+    /// File to write the summary to, or `-` for stdout
+    #[clap(long, default_value = "-")]
+    pub summary: SummaryTarget,
+
+    /// Workflow file to load the inputs from, e.g. .github/workflows/review.yml
+    #[clap(long)]
+    pub workflow: Option<PathBuf>,
+
+    /// Id or name of the workflow step to load; defaults to the only step
+    /// using iflow-cli-action
+    #[clap(long, requires = "workflow")]
+    pub step: Option<String>,
+}
+
+/// Options of the `summarize` subcommand
+#[derive(clap::Args, Debug, Clone)]
+pub struct SummarizeArgs {
+    /// Log of the run, ideally written with --log-format json
+    pub transcript: PathBuf,
+
+    /// File to write the summary to, or `-` for stdout
+    #[clap(long, default_value = "-")]
+    pub summary: SummaryTarget,
+}
+
+impl CliArgs {
+    /// Options of the `run` subcommand, `None` when running as the action or
+    /// another subcommand
+    pub fn run_args(&self) -> Option<&RunArgs> {
+        match &self.command {
+            Some(Command::Run(run)) => Some(run),
+            _ => None,
+        }
+    }
+
+    /// Builds the iFlow settings configuration from the inputs
+    pub fn iflow_config(&self) -> IFlowConfig {
+        IFlowConfig {
+            base_url: self.base_url.clone(),
+            model: self.model.clone(),
+            auth_type: self.auth_type,
+            search_api_key: self.search_api_key.clone(),
+            theme: self.theme.clone(),
+            max_session_turns: self.max_turns,
+            otlp_endpoint: self.otlp_endpoint.clone(),
+        }
+    }
+
+    /// Builds the retry policy from the retry inputs
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            initial_delay: std::time::Duration::from_secs_f64(self.retry_initial_delay),
+            max_delay: std::time::Duration::from_secs_f64(self.retry_max_delay),
+            jitter: self.retry_jitter,
+            retry_on: self.retry_on.clone(),
+        }
+    }
+
+    /// Builds the session budget from the budget inputs
+    pub fn budget(&self) -> SessionBudget {
+        SessionBudget {
+            max_turns: self.max_turns,
+            max_tool_calls: self.max_tool_calls,
+            max_tokens: self.max_tokens,
+        }
+    }
+
+    /// Builds the watchdog settings from the idle timeout and heartbeat inputs
+    pub fn watchdog(&self) -> WatchdogSettings<'_> {
+        let seconds = |secs: u32| Some(std::time::Duration::from_secs(secs.into()));
+        WatchdogSettings {
+            idle_timeout: self.idle_timeout.and_then(seconds),
+            retry_prompt: self
+                .idle_retry_prompt
+                .as_deref()
+                .filter(|p| !p.trim().is_empty()),
+            heartbeat_interval: Some(self.heartbeat_interval)
+                .filter(|secs| *secs > 0)
+                .and_then(seconds),
+        }
+    }
+
+    /// Parses the model price table; validated before use
+    pub fn model_prices(&self) -> HashMap<String, ModelPrice> {
+        self.model_prices
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .and_then(|v| parse_model_prices(v).ok())
+            .unwrap_or_default()
+    }
+
+    /// Loads the output schema; validated before use
+    pub fn output_schema(&self) -> Option<OutputSchema> {
+        self.output_schema
+            .as_deref()
+            .filter(|v| !v.trim().is_empty())
+            .and_then(|v| OutputSchema::load(v).ok())
+    }
+
+    /// Returns the primary model followed by the configured fallback models
+    pub fn models(&self) -> Vec<String> {
+        let mut models = vec![self.model.clone()];
+        if let Some(fallback_models) = &self.fallback_models {
+            for model in fallback_models.split([',', '\n']).map(str::trim) {
+                if !model.is_empty() && !models.iter().any(|m| m == model) {
+                    models.push(model.to_string());
+                }
+            }
+        }
+        models
+    }
+}
@@ -0,0 +1,228 @@
+use crate::cli::args::{CliArgs, SummarizeArgs};
+use crate::cli::validation::validate_args;
+use crate::error::{Error, Result};
+use crate::github::api::write_curl_header;
+use crate::github::outputs::{capture_step_summary, flush_local_summary, write_step_summary};
+use crate::github::summary::{generate_summary_markdown, parse_transcript};
+use crate::iflow::acp_client::IFLOW_PORT;
+use crate::logging;
+use crate::secrets::redact_settings;
+use crate::version_mgr::{
+    MIN_IFLOW_VERSION, check_iflow_compatibility, get_command_version, parse_version, url_host,
+};
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+
+/// Number of ports after [`IFLOW_PORT`] that iFlow CLI tries when it is taken
+const PORT_RANGE: u16 = 100;
+
+/// Outcome of a `doctor` check
+#[derive(Debug)]
+pub enum Check {
+    Passed(String),
+    /// A problem that does not keep the action from running
+    Warning(String),
+    Failed(Error),
+}
+
+/// The settings.json the run would write, parsed
+fn effective_settings(cli: &CliArgs) -> Result<Value> {
+    let settings = cli.iflow_config().settings(
+        cli.settings_json.as_ref(),
+        cli.api_key.as_deref().unwrap_or(""),
+    )?;
+    serde_json::from_str(&settings)
+        .map_err(|e| Error::Configuration(format!("invalid settings: {}", e)))
+}
+
+/// `validate`: checks the inputs and the settings they produce
+pub fn validate(cli: &CliArgs) -> Result<()> {
+    validate_args(cli)?;
+    effective_settings(cli)?;
+    logging::info("✅ Inputs and settings are valid");
+    Ok(())
+}
+
+/// `print-settings`: prints the settings.json the run would write, with
+/// credentials redacted
+pub fn print_settings(cli: &CliArgs) -> Result<()> {
+    let settings = redact_settings(&effective_settings(cli)?);
+    let settings = serde_json::to_string_pretty(&settings)
+        .map_err(|e| Error::Configuration(format!("failed to format settings: {}", e)))?;
+    println!("{}", settings);
+    Ok(())
+}
+
+/// `doctor`: checks the tools, the port and the API the run depends on, and
+/// fails with the error of the first failed check
+pub fn doctor(cli: &CliArgs) -> Result<()> {
+    let settings = effective_settings(cli)?;
+    let base_url = settings["baseUrl"].as_str().unwrap_or(&cli.base_url);
+    let api_key = settings["apiKey"].as_str().filter(|key| !key.is_empty());
+
+    let checks = [
+        ("iFlow CLI", check_iflow()),
+        ("Node.js", check_tool("node", true)),
+        ("GitHub CLI", check_tool("gh", false)),
+        ("Port", check_port(IFLOW_PORT)),
+        ("API", check_api(base_url, api_key)),
+    ];
+
+    let mut failure = None;
+    for (name, check) in checks {
+        match check {
+            Check::Passed(detail) => logging::info(&format!("✅ {}: {}", name, detail)),
+            Check::Warning(detail) => logging::warn(&format!("⚠️  {}: {}", name, detail)),
+            Check::Failed(err) => {
+                logging::error(&format!("❌ {}: {}", name, err));
+                failure.get_or_insert(err);
+            }
+        }
+    }
+    match failure {
+        Some(err) => Err(err),
+        None => {
+            logging::info("🩺 All checks passed");
+            Ok(())
+        }
+    }
+}
+
+/// The iFlow CLI is installed and recent enough for the SDK
+fn check_iflow() -> Check {
+    let version = match check_tool("iflow", true) {
+        Check::Passed(version) => version,
+        other => return other,
+    };
+    match check_iflow_compatibility() {
+        Ok(()) => Check::Passed(format!(
+            "{} (requires {} or newer)",
+            version, MIN_IFLOW_VERSION
+        )),
+        Err(e) => Check::Failed(e),
+    }
+}
+
+/// `command` is installed; a missing optional tool is only a warning
+fn check_tool(command: &str, required: bool) -> Check {
+    match get_command_version(command) {
+        Ok(output) => Check::Passed(parse_version(&output).unwrap_or(output)),
+        Err(e) if required => Check::Failed(e),
+        Err(e) => Check::Warning(e.to_string()),
+    }
+}
+
+/// A port for the auto-started iFlow CLI is free
+pub fn check_port(port: u16) -> Check {
+    let free = (port..port.saturating_add(PORT_RANGE))
+        .find(|port| TcpListener::bind(("localhost", *port)).is_ok());
+    match free {
+        Some(free) if free == port => Check::Passed(format!("{} is free", port)),
+        Some(free) => Check::Warning(format!(
+            "{} is in use, iFlow CLI will listen on {}",
+            port, free
+        )),
+        None => Check::Failed(Error::Connection(format!(
+            "no free port between {} and {}",
+            port,
+            port.saturating_add(PORT_RANGE - 1)
+        ))),
+    }
+}
+
+/// `base_url` answers, and accepts the API key when there is one
+fn check_api(base_url: &str, api_key: Option<&str>) -> Check {
+    let url = format!("{}/models", base_url.trim_end_matches('/'));
+    let host = url_host(base_url).unwrap_or_else(|| base_url.to_string());
+    let unreachable = |msg: String| {
+        Check::Failed(Error::Connection(format!(
+            "{} is not reachable: {}",
+            host, msg
+        )))
+    };
+
+    // The API key is passed to curl on stdin so it never appears in the process list
+    let child = Command::new("curl")
+        .args(["-sS", "-o", "/dev/null", "-w", "%{http_code}"])
+        .args(["--max-time", "10", "--config", "-"])
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => return unreachable(format!("failed to run curl: {}", e)),
+    };
+    if let (Some(mut stdin), Some(api_key)) = (child.stdin.take(), api_key) {
+        let _ = write_curl_header(&mut stdin, &format!("Authorization: Bearer {}", api_key));
+    }
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(e) => return unreachable(format!("failed to run curl: {}", e)),
+    };
+    if !output.status.success() {
+        return unreachable(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match status.as_str() {
+        "401" | "403" if api_key.is_some() => Check::Failed(Error::Authentication(format!(
+            "{} rejected the API key (HTTP {})",
+            host, status
+        ))),
+        _ => Check::Passed(format!("{} is reachable (HTTP {})", host, status)),
+    }
+}
+
+/// Settings of the summary given on the command line (`matches` are those of
+/// `summarize`). The log does not record the settings of the run, so only those
+/// given now are shown; defaults would claim values the run may not have used.
+pub fn given_settings(cli: &CliArgs, matches: &ArgMatches) -> HashMap<&'static str, Value> {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let mut settings = HashMap::new();
+    if given("model") {
+        settings.insert("model", json!(cli.model));
+    }
+    if given("base_url") {
+        settings.insert("baseURL", json!(cli.base_url));
+    }
+    if given("timeout") {
+        settings.insert("timeout", json!(cli.timeout));
+    }
+    if given("working_directory") {
+        settings.insert("workingDir", json!(cli.working_directory));
+    }
+    if let Some(prompt) = cli.prompt.as_deref().filter(|_| given("prompt")) {
+        settings.insert("prompt", json!(prompt));
+    }
+    settings
+}
+
+/// `summarize`: renders the summary of a run from its saved log; `matches` are
+/// the argument matches of the subcommand
+pub fn summarize(cli: &CliArgs, args: &SummarizeArgs, matches: &ArgMatches) -> Result<()> {
+    let log = std::fs::read_to_string(&args.transcript).map_err(|e| {
+        Error::Validation(format!(
+            "failed to read transcript '{}': {}",
+            args.transcript.display(),
+            e
+        ))
+    })?;
+    let transcript = parse_transcript(&log);
+
+    let mut config = transcript.config;
+    config.extend(given_settings(cli, matches));
+
+    capture_step_summary(args.summary.clone());
+    write_step_summary(&generate_summary_markdown(
+        &transcript.messages,
+        transcript.exit_code,
+        &config,
+    ))?;
+    flush_local_summary()
+}
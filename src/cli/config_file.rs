@@ -0,0 +1,161 @@
+use crate::cli::args::CliArgs;
+use crate::error::{Error, Result};
+use clap::CommandFactory;
+use serde_json::Value;
+use std::path::Path;
+
+/// Inputs that hold credentials, which do not belong in a file in the repository
+const SECRET_INPUTS: &[&str] = &["api_key", "search_api_key", "github_token"];
+
+/// Inputs that select the config file itself
+const CONFIG_INPUTS: &[&str] = &["config_file", "profile"];
+
+/// Inputs loaded from a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigInputs {
+    /// Profile applied on top of the file's defaults
+    pub profile: Option<String>,
+    /// Input names and values, the profile's values replacing the defaults
+    pub inputs: Vec<(String, String)>,
+}
+
+/// Loads the inputs of a YAML or TOML config file (TOML when the file ends
+/// in `.toml`), applying `profile` on top of its defaults
+pub fn load_config_inputs(path: &Path, profile: Option<&str>) -> Result<ConfigInputs> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        Error::Validation(format!(
+            "failed to read config file '{}': {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let toml = path.extension().is_some_and(|ext| ext == "toml");
+    parse_config_inputs(&content, toml, profile)
+}
+
+/// Reads the `defaults` and the selected entry of `profiles` of a config
+/// file, e.g.
+///
+/// ```yaml
+/// defaults:
+///   model: kimi-k2
+///   timeout: 1800
+/// profiles:
+///   review:
+///     model: qwen3-coder-plus
+///     prompt: Review the pull request
+/// ```
+///
+/// Keys are input names; lists are joined with commas and mappings are
+/// passed as JSON, e.g. for `output_schema`
+pub fn parse_config_inputs(
+    content: &str,
+    toml: bool,
+    profile: Option<&str>,
+) -> Result<ConfigInputs> {
+    let invalid = |msg: String| Error::Validation(format!("invalid config file: {}", msg));
+    let config: Value = if toml {
+        toml::from_str(content).map_err(|e| invalid(e.to_string()))?
+    } else {
+        serde_yaml_ng::from_str(content).map_err(|e| invalid(e.to_string()))?
+    };
+    let config = match config {
+        Value::Object(config) => config,
+        // An empty YAML file has no defaults
+        Value::Null => Default::default(),
+        _ => return Err(invalid("expected a mapping".to_string())),
+    };
+    if let Some(key) = config
+        .keys()
+        .find(|key| !["defaults", "profiles"].contains(&key.as_str()))
+    {
+        return Err(invalid(format!(
+            "unknown key '{}', expected defaults and profiles",
+            key
+        )));
+    }
+
+    let mut inputs = section_inputs(config.get("defaults").unwrap_or(&Value::Null), "defaults")
+        .map_err(invalid)?;
+    if let Some(name) = profile {
+        let profiles = config.get("profiles").and_then(Value::as_object);
+        let Some(section) = profiles.and_then(|profiles| profiles.get(name)) else {
+            let available: Vec<&str> = profiles
+                .into_iter()
+                .flat_map(|profiles| profiles.keys())
+                .map(String::as_str)
+                .collect();
+            return Err(invalid(format!(
+                "no profile '{}', available: {}",
+                name,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )));
+        };
+        for (name, value) in
+            section_inputs(section, &format!("profiles.{}", name)).map_err(invalid)?
+        {
+            inputs.retain(|(existing, _)| *existing != name);
+            inputs.push((name, value));
+        }
+    }
+
+    Ok(ConfigInputs {
+        profile: profile.map(str::to_string),
+        inputs,
+    })
+}
+
+/// Inputs of a `defaults` or profile section; `name` locates it in errors
+fn section_inputs(
+    section: &Value,
+    name: &str,
+) -> std::result::Result<Vec<(String, String)>, String> {
+    let section = match section {
+        Value::Object(section) => section,
+        Value::Null => return Ok(Vec::new()),
+        _ => return Err(format!("{} must be a mapping of inputs", name)),
+    };
+
+    let known = known_inputs();
+    let mut inputs = Vec::new();
+    for (key, value) in section {
+        let input = key.replace('-', "_");
+        if SECRET_INPUTS.contains(&input.as_str()) {
+            return Err(format!(
+                "{}.{} is a secret, pass it as an input instead",
+                name, key
+            ));
+        }
+        if CONFIG_INPUTS.contains(&input.as_str()) || !known.contains(&input) {
+            return Err(format!("{}.{} is not an input", name, key));
+        }
+        let value = match value {
+            Value::Null => continue,
+            Value::String(s) => s.clone(),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            other => other.to_string(),
+        };
+        inputs.push((input, value));
+    }
+    Ok(inputs)
+}
+
+/// Names of the action's inputs, i.e. the arguments read from `INPUT_*`
+fn known_inputs() -> Vec<String> {
+    CliArgs::command()
+        .get_arguments()
+        .filter_map(|arg| arg.get_env())
+        .filter_map(|env| env.to_str()?.strip_prefix("INPUT_").map(str::to_lowercase))
+        .collect()
+}
@@ -3,6 +3,12 @@
 //! This module contains all command-line interface functionality including:
 //! - Command-line argument parsing
 //! - Input validation
+//! - Loading inputs from a workflow step for local runs
+//! - Loading default inputs and profiles from a config file
+//! - The `validate`, `print-settings`, `doctor` and `summarize` subcommands
 
 pub mod args;
+pub mod commands;
+pub mod config_file;
 pub mod validation;
+pub mod workflow;
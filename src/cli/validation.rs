@@ -1,32 +1,192 @@
+use crate::cli::args::CliArgs;
+use crate::cli::config_file::load_config_inputs;
+use crate::error::{Error, Result};
+use crate::iflow::config::AuthType;
+use crate::iflow::metrics::parse_model_prices;
+use crate::iflow::structured::OutputSchema;
+use crate::version_mgr::VersionSpec;
+
+/// Default iFlow API endpoint, which cannot serve other providers' models
+const IFLOW_BASE_URL: &str = "https://apis.iflow.cn/v1";
+
 /// Validates CLI arguments
-pub fn validate_args(
-    prompt: Option<&String>,
-    api_key: Option<&String>,
-    settings_json: Option<&String>,
-    timeout: u32,
-) -> Result<(), String> {
-    // Validate required inputs
-    if prompt.is_none_or(|p| p.is_empty()) {
-        return Err("prompt input is required and cannot be empty".to_string());
+pub fn validate_args(args: &CliArgs) -> Result<()> {
+    let has_value = |v: &Option<String>| v.as_ref().is_some_and(|v| !v.is_empty());
+
+    // Validate required inputs; triage builds its own prompt
+    if !args.triage && !has_value(&args.prompt) {
+        return Err(Error::Validation(
+            "prompt input is required and cannot be empty".to_string(),
+        ));
     }
 
-    if api_key.is_none() && settings_json.is_none() {
-        return Err("api_key input is required and cannot be empty".to_string());
+    // A profile is selected from the config file, which must be valid
+    let profile = args.profile.as_deref().filter(|p| !p.is_empty());
+    match args.config_file.as_deref() {
+        Some(config_file) if !config_file.as_os_str().is_empty() => {
+            load_config_inputs(config_file, profile)?;
+        }
+        _ if profile.is_some() => {
+            return Err(Error::Validation(
+                "profile requires a config_file to select it from".to_string(),
+            ));
+        }
+        _ => {}
     }
 
     // Validate timeout range (1 second to 24 hours)
-    if !(1..=86400).contains(&timeout) {
-        return Err(
+    if !(1..=86400).contains(&args.timeout) {
+        return Err(Error::Validation(
             "timeout value is out of range. Must be between 1 and 86400 seconds".to_string(),
-        );
+        ));
+    }
+
+    // Validate the watchdog; the idle timeout only helps below the total timeout
+    if let Some(idle_timeout) = args.idle_timeout
+        && !(1..=args.timeout).contains(&idle_timeout)
+    {
+        return Err(Error::Validation(format!(
+            "idle_timeout value is out of range. Must be between 1 and the timeout ({} seconds)",
+            args.timeout
+        )));
+    }
+    if args.heartbeat_interval > 86400 {
+        return Err(Error::Validation(
+            "heartbeat_interval value is out of range. Must be between 0 and 86400 seconds"
+                .to_string(),
+        ));
+    }
+
+    // Validate retry settings
+    if args.max_retries > 10 {
+        return Err(Error::Validation(
+            "max_retries value is out of range. Must be between 0 and 10".to_string(),
+        ));
+    }
+    if !(0.0..=3600.0).contains(&args.retry_initial_delay)
+        || !(0.0..=3600.0).contains(&args.retry_max_delay)
+    {
+        return Err(Error::Validation(
+            "retry delays are out of range. Must be between 0 and 3600 seconds".to_string(),
+        ));
     }
 
-    // Validate settings_json if provided
-    if let Some(settings_json) = settings_json
+    // Validate budgets; a zero budget would cancel every session immediately
+    if args.max_turns == Some(0) || args.max_tool_calls == Some(0) || args.max_tokens == Some(0) {
+        return Err(Error::Validation(
+            "max_turns, max_tool_calls and max_tokens must be greater than 0".to_string(),
+        ));
+    }
+
+    // iFlow's WebSocket transport reports every finished prompt as completed, so
+    // no other stop reason could ever fail the step
+    if !args.fail_on_stop_reason.is_empty() {
+        return Err(Error::Validation(
+            "fail_on_stop_reason is not supported: iFlow does not report the stop reason over WebSocket".to_string(),
+        ));
+    }
+
+    if let Some(model_prices) = args.model_prices.as_deref().filter(|v| !v.is_empty()) {
+        parse_model_prices(model_prices)?;
+    }
+
+    if let Some(output_schema) = args
+        .output_schema
+        .as_deref()
+        .filter(|v| !v.trim().is_empty())
+    {
+        OutputSchema::load(output_schema)?;
+    }
+    if args.triage {
+        if has_value(&args.output_schema) {
+            return Err(Error::Validation(
+                "output_schema cannot be combined with triage, which defines its own schema"
+                    .to_string(),
+            ));
+        }
+        if !has_value(&args.github_token) {
+            return Err(Error::Validation(
+                "triage requires a github_token to read the issue and apply labels".to_string(),
+            ));
+        }
+    }
+    if args.output_schema_retries > 10 {
+        return Err(Error::Validation(
+            "output_schema_retries value is out of range. Must be between 0 and 10".to_string(),
+        ));
+    }
+
+    // Validate version specs before anything is installed
+    for version in [&args.gh_version, &args.iflow_version]
+        .into_iter()
+        .flatten()
+    {
+        if !version.is_empty() {
+            VersionSpec::parse(version)?;
+        }
+    }
+
+    // Offline installs cannot resolve ranges against the registry
+    if has_value(&args.npm_cache)
+        && !has_value(&args.iflow_tarball)
+        && let Some(version) = args.iflow_version.as_deref().filter(|v| !v.is_empty())
+        && !matches!(VersionSpec::parse(version)?, VersionSpec::Exact(_))
+    {
+        return Err(Error::Validation(
+            "iflow_version must be an exact version when installing from npm_cache".to_string(),
+        ));
+    }
+
+    // Local tarballs must exist before anything is installed
+    for tarball in [&args.gh_tarball, &args.iflow_tarball]
+        .into_iter()
+        .flatten()
+        .filter(|v| !v.is_empty())
+    {
+        if !std::path::Path::new(tarball).is_file() {
+            return Err(Error::Validation(format!(
+                "tarball '{}' does not exist",
+                tarball
+            )));
+        }
+    }
+
+    // Validate settings_json if provided; it carries its own credentials
+    if let Some(settings_json) = &args.settings_json
         && !settings_json.is_empty()
     {
         serde_json::from_str::<serde_json::Value>(settings_json)
-            .map_err(|e| format!("invalid settings_json provided: {}", e))?;
+            .map_err(|e| Error::Validation(format!("invalid settings_json provided: {}", e)))?;
+        return Ok(());
+    }
+
+    if !args.base_url.starts_with("http://") && !args.base_url.starts_with("https://") {
+        return Err(Error::Validation(format!(
+            "base_url must be an http(s) URL, got '{}'",
+            args.base_url
+        )));
+    }
+
+    // Validate credentials required by the selected auth type
+    match args.auth_type {
+        AuthType::Iflow => {
+            if !has_value(&args.api_key) {
+                return Err(Error::Validation(
+                    "api_key input is required and cannot be empty".to_string(),
+                ));
+            }
+        }
+        AuthType::OpenaiCompatible => {
+            if !has_value(&args.api_key) {
+                return Err(Error::Validation(
+                    "api_key input is required for auth_type 'openai-compatible'".to_string(),
+                ));
+            }
+            if args.base_url.trim_end_matches('/') == IFLOW_BASE_URL {
+                return Err(Error::Validation("base_url must point to your OpenAI-compatible endpoint when auth_type is 'openai-compatible'"
+                        .to_string()));
+            }
+        }
     }
 
     Ok(())
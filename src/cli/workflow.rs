@@ -0,0 +1,144 @@
+use crate::error::{Error, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// Inputs of an iFlow step loaded from a workflow file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepInputs {
+    /// Id or name of the step, or its index when it has neither
+    pub step: String,
+    /// Input names and values from the step's `with:` block
+    pub inputs: Vec<(String, String)>,
+    /// Inputs left out because an expression could not be resolved locally
+    pub skipped: Vec<String>,
+}
+
+/// Environment variable GitHub Actions passes an input in, e.g. `INPUT_API_KEY`
+pub fn input_env_var(name: &str) -> String {
+    format!("INPUT_{}", name.replace(' ', "_").to_uppercase())
+}
+
+/// Loads the `with:` block of a step in a workflow file, resolving
+/// `secrets`, `vars` and `env` expressions from environment variables
+pub fn load_step_inputs(path: &Path, step: Option<&str>) -> Result<StepInputs> {
+    let workflow = std::fs::read_to_string(path).map_err(|e| {
+        Error::Validation(format!(
+            "failed to read workflow '{}': {}",
+            path.display(),
+            e
+        ))
+    })?;
+    parse_step_inputs(&workflow, step, |name| std::env::var(name).ok())
+}
+
+/// Reads the `with:` block of the step with the given id or name, or of the
+/// only step using this action; `lookup` resolves the names in `secrets`,
+/// `vars` and `env` expressions
+pub fn parse_step_inputs(
+    workflow: &str,
+    step: Option<&str>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<StepInputs> {
+    let invalid = |msg: String| Error::Validation(format!("invalid workflow: {}", msg));
+    let workflow: Value = serde_yaml_ng::from_str(workflow).map_err(|e| invalid(e.to_string()))?;
+
+    let steps: Vec<(String, &Value)> = workflow["jobs"]
+        .as_object()
+        .ok_or_else(|| invalid("no jobs found".to_string()))?
+        .values()
+        .filter_map(|job| job["steps"].as_array())
+        .flat_map(|steps| steps.iter().enumerate())
+        .map(|(index, step)| {
+            let label = step["id"]
+                .as_str()
+                .or_else(|| step["name"].as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("step {}", index + 1));
+            (label, step)
+        })
+        .collect();
+
+    let matching: Vec<&(String, &Value)> = match step {
+        Some(wanted) => steps
+            .iter()
+            .filter(|(_, s)| {
+                [&s["id"], &s["name"]]
+                    .iter()
+                    .any(|v| v.as_str() == Some(wanted))
+            })
+            .collect(),
+        None => steps
+            .iter()
+            .filter(|(_, s)| {
+                s["uses"]
+                    .as_str()
+                    .is_some_and(|u| u.contains("iflow-cli-action"))
+            })
+            .collect(),
+    };
+    let (label, found) = match (matching.as_slice(), step) {
+        ([only], _) => only,
+        ([], Some(wanted)) => {
+            return Err(invalid(format!("no step with id or name '{}'", wanted)));
+        }
+        ([], None) => return Err(invalid("no step uses iflow-cli-action".to_string())),
+        (several, _) => {
+            let labels: Vec<&str> = several.iter().map(|(label, _)| label.as_str()).collect();
+            return Err(invalid(format!(
+                "several steps match, select one with --step: {}",
+                labels.join(", ")
+            )));
+        }
+    };
+
+    let mut inputs = Vec::new();
+    let mut skipped = Vec::new();
+    for (name, value) in found["with"].as_object().into_iter().flatten() {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        match resolve_expressions(&value, &lookup) {
+            Ok(value) => inputs.push((name.clone(), value)),
+            Err(expression) => skipped.push(format!("{} (${{{{ {} }}}})", name, expression)),
+        }
+    }
+
+    Ok(StepInputs {
+        step: label.clone(),
+        inputs,
+        skipped,
+    })
+}
+
+/// Replaces every `${{ secrets.NAME }}`, `${{ vars.NAME }}` and
+/// `${{ env.NAME }}` with the value of `NAME`; returns the first expression
+/// that cannot be resolved
+fn resolve_expressions(
+    value: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let mut resolved = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${{") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or_else(|| rest[start + 3..].trim().to_string())?;
+        let expression = rest[start + 3..end].trim();
+        let name = ["secrets.", "vars.", "env."]
+            .iter()
+            .find_map(|prefix| expression.strip_prefix(prefix))
+            .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        let Some(substitute) = name.and_then(lookup) else {
+            return Err(expression.to_string());
+        };
+
+        resolved.push_str(&rest[..start]);
+        resolved.push_str(&substitute);
+        rest = &rest[end + 2..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
@@ -0,0 +1,156 @@
+use crate::iflow::retry::ErrorClass;
+use std::fmt;
+
+/// Result type used throughout the action
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors produced by the action, grouped by the stage that failed.
+///
+/// Each category maps to a distinct process exit code and an `error_kind`
+/// output so workflows can branch on the failure type:
+///
+/// | Kind             | Exit code |
+/// |------------------|-----------|
+/// | `session`        | 1         |
+/// | `validation`     | 2         |
+/// | `configuration`  | 3         |
+/// | `install`        | 4         |
+/// | `precmd`         | 5         |
+/// | `connection`     | 6         |
+/// | `model`          | 7         |
+/// | `output`         | 8         |
+/// | `authentication` | 9         |
+/// | `budget`         | 10        |
+/// | `schema`         | 11        |
+/// | `github`         | 12        |
+/// | `stalled`        | 13        |
+/// | `stop_reason`    | 14        |
+/// | `cancelled`      | 130       |
+/// | `timeout`        | 124       |
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid or missing inputs
+    Validation(String),
+    /// Writing or updating iFlow settings failed
+    Configuration(String),
+    /// Installing or inspecting GitHub CLI / iFlow CLI failed
+    Install(String),
+    /// A pre-command failed
+    Precmd(String),
+    /// Connecting to iFlow or delivering the prompt failed at the transport level
+    Connection(String),
+    /// Credentials were rejected; retrying or switching models cannot help
+    Authentication(String),
+    /// The model reported an error, e.g. overloaded or unavailable
+    Model { code: i32, message: String },
+    /// The session did not finish within the configured timeout
+    Timeout(String),
+    /// No message arrived from iFlow within the idle timeout
+    Stalled(String),
+    /// The agent stopped with a stop reason listed in `fail_on_stop_reason`
+    StopReason(String),
+    /// The session exceeded its turn, tool call or token budget
+    Budget(String),
+    /// The structured result did not match `output_schema`, even after re-prompting
+    Schema(String),
+    /// A GitHub API request failed, e.g. fetching an issue or applying labels
+    GitHub(String),
+    /// The run was cancelled by SIGTERM or SIGINT, e.g. a cancelled workflow
+    Cancelled(String),
+    /// Writing GitHub Actions outputs or the step summary failed
+    Output(String),
+    /// Any other failure of the agent session
+    Session(String),
+}
+
+impl Error {
+    /// Process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Session(_) => 1,
+            Error::Validation(_) => 2,
+            Error::Configuration(_) => 3,
+            Error::Install(_) => 4,
+            Error::Precmd(_) => 5,
+            Error::Connection(_) => 6,
+            Error::Model { .. } => 7,
+            Error::Output(_) => 8,
+            Error::Authentication(_) => 9,
+            Error::Budget(_) => 10,
+            Error::Schema(_) => 11,
+            Error::GitHub(_) => 12,
+            Error::Stalled(_) => 13,
+            Error::StopReason(_) => 14,
+            Error::Timeout(_) => 124,
+            Error::Cancelled(_) => 130,
+        }
+    }
+
+    /// Value of the `error_kind` output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Validation(_) => "validation",
+            Error::Configuration(_) => "configuration",
+            Error::Install(_) => "install",
+            Error::Precmd(_) => "precmd",
+            Error::Connection(_) => "connection",
+            Error::Authentication(_) => "authentication",
+            Error::Model { .. } => "model",
+            Error::Timeout(_) => "timeout",
+            Error::Stalled(_) => "stalled",
+            Error::StopReason(_) => "stop_reason",
+            Error::Budget(_) => "budget",
+            Error::Schema(_) => "schema",
+            Error::GitHub(_) => "github",
+            Error::Cancelled(_) => "cancelled",
+            Error::Output(_) => "output",
+            Error::Session(_) => "session",
+        }
+    }
+
+    /// Whether another model may succeed where this error occurred
+    pub fn is_fallback_eligible(&self, fallback_error_codes: &[i32]) -> bool {
+        match self {
+            Error::Connection(_) => true,
+            Error::Model { code, .. } => fallback_error_codes.contains(code),
+            _ => false,
+        }
+    }
+
+    /// Transient error class used by the retry policy, `None` for permanent errors
+    pub fn retry_class(&self) -> Option<ErrorClass> {
+        match self {
+            Error::Connection(_) => Some(ErrorClass::Connection),
+            Error::Model { code, .. } => ErrorClass::from_code(*code),
+            Error::Timeout(_) => Some(ErrorClass::Timeout),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Validation(msg) => write!(f, "Validation Error: {}", msg),
+            Error::Configuration(msg) => write!(f, "Configuration Error: {}", msg),
+            Error::Install(msg) => write!(f, "Installation Error: {}", msg),
+            Error::Precmd(msg) => write!(f, "Pre-command Error: {}", msg),
+            Error::Connection(msg) => write!(f, "Connection Error: {}", msg),
+            Error::Authentication(msg) => write!(f, "Authentication Error: {}", msg),
+            Error::Model { code, message } => write!(f, "Model Error {}: {}", code, message),
+            Error::Timeout(msg) => write!(f, "Timeout Error: {}", msg),
+            Error::Stalled(msg) => write!(f, "Stalled: {}", msg),
+            Error::StopReason(reason) => {
+                write!(f, "Stop Reason Error: the agent stopped with {}", reason)
+            }
+            Error::Budget(msg) => write!(f, "Budget Exceeded: {}", msg),
+            Error::Schema(msg) => write!(f, "Schema Error: {}", msg),
+            Error::GitHub(msg) => write!(f, "GitHub API Error: {}", msg),
+            Error::Cancelled(msg) => write!(f, "Cancelled: {}", msg),
+            Error::Output(msg) => write!(f, "Output Error: {}", msg),
+            Error::Session(msg) => write!(f, "Session Error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,7 +1,10 @@
+use crate::error::{Error, Result};
+use crate::logging::{self, Level};
+use serde_json::json;
 use std::process::Command;
 
 /// Executes pre-command if specified
-pub fn execute_precmd(precmd: &Option<String>, working_directory: &str) -> Result<(), String> {
+pub fn execute_precmd(precmd: &Option<String>, working_directory: &str) -> Result<()> {
     if let Some(precmd) = precmd
         && !precmd.is_empty()
     {
@@ -15,7 +18,7 @@ pub fn execute_precmd(precmd: &Option<String>, working_directory: &str) -> Resul
                 continue;
             }
 
-            println!("Executing pre-command: {}", command);
+            logging::info(&format!("Executing pre-command: {}", command));
 
             // Create a command to execute the pre-command
             let output = Command::new("sh")
@@ -23,23 +26,38 @@ pub fn execute_precmd(precmd: &Option<String>, working_directory: &str) -> Resul
                 .arg(command)
                 .current_dir(working_directory)
                 .output()
-                .map_err(|e| format!("failed to execute pre-command '{}': {}", command, e))?;
+                .map_err(|e| {
+                    Error::Precmd(format!(
+                        "failed to execute pre-command '{}': {}",
+                        command, e
+                    ))
+                })?;
 
             // Print stdout and stderr
             if !output.stdout.is_empty() {
-                println!("{}", String::from_utf8_lossy(&output.stdout));
+                logging::event(
+                    Level::Info,
+                    "precmd_output",
+                    &String::from_utf8_lossy(&output.stdout),
+                    json!({ "stream": "stdout" }),
+                );
             }
             if !output.stderr.is_empty() {
-                eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+                logging::event(
+                    Level::Warn,
+                    "precmd_output",
+                    &String::from_utf8_lossy(&output.stderr),
+                    json!({ "stream": "stderr" }),
+                );
             }
 
             // Check if command failed
             if !output.status.success() {
-                return Err(format!(
+                return Err(Error::Precmd(format!(
                     "pre-command '{}' failed with exit code: {:?}",
                     command,
                     output.status.code()
-                ));
+                )));
             }
         }
     }
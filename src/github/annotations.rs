@@ -0,0 +1,143 @@
+use crate::logging::{self, LogFormat};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Severity of a workflow annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Error,
+}
+
+impl AnnotationLevel {
+    fn command(&self) -> &'static str {
+        match self {
+            AnnotationLevel::Notice => "notice",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Error => "error",
+        }
+    }
+}
+
+/// Source location attached to an annotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: Option<u32>,
+}
+
+impl Location {
+    /// Extracts a location from the details of an agent error, which may carry
+    /// `file`/`path` and `line` entries
+    pub fn from_details(details: &HashMap<String, Value>) -> Option<Location> {
+        let file = ["file", "path", "filePath"]
+            .iter()
+            .find_map(|key| details.get(*key)?.as_str())
+            .filter(|file| !file.is_empty())?;
+        let line = ["line", "lineNumber"].iter().find_map(|key| {
+            let line = details.get(*key)?;
+            line.as_u64()
+                .or_else(|| line.as_str()?.parse().ok())
+                .and_then(|line| u32::try_from(line).ok())
+        });
+
+        Some(Location {
+            file: file.to_string(),
+            line,
+        })
+    }
+}
+
+/// Escapes the message of a workflow command
+pub fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property value of a workflow command
+pub fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Formats an annotation workflow command, e.g. `::error file=src/main.rs,line=3::message`
+pub fn annotation(level: AnnotationLevel, message: &str, location: Option<&Location>) -> String {
+    let mut properties = Vec::new();
+    if let Some(location) = location {
+        properties.push(format!("file={}", escape_property(&location.file)));
+        if let Some(line) = location.line {
+            properties.push(format!("line={}", line));
+        }
+    }
+
+    let properties = if properties.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", properties.join(","))
+    };
+    format!(
+        "::{}{}::{}",
+        level.command(),
+        properties,
+        escape_data(message)
+    )
+}
+
+/// Emits an annotation when running in GitHub Actions
+pub fn annotate(level: AnnotationLevel, message: &str, location: Option<&Location>) {
+    if std::env::var("GITHUB_ACTIONS").is_ok() {
+        logging::workflow_command(&annotation(level, message, location));
+    }
+}
+
+/// Collapsible log groups around agent activity.
+///
+/// GitHub does not nest groups, so opening a group ends the current one.
+/// Groups are only written in GitHub Actions and not in `json` log format.
+#[derive(Debug)]
+pub struct LogGroups {
+    enabled: bool,
+    open: bool,
+}
+
+impl LogGroups {
+    pub fn new() -> Self {
+        LogGroups {
+            enabled: std::env::var("GITHUB_ACTIONS").is_ok()
+                && logging::format() != LogFormat::Json,
+            open: false,
+        }
+    }
+
+    /// Starts a group titled `title`, ending the current one
+    pub fn open(&mut self, title: &str) {
+        if !self.enabled {
+            return;
+        }
+        self.close();
+        logging::workflow_command(&format!("::group::{}", escape_data(title)));
+        self.open = true;
+    }
+
+    /// Ends the current group, if any
+    pub fn close(&mut self) {
+        if self.open {
+            logging::workflow_command("::endgroup::");
+            self.open = false;
+        }
+    }
+}
+
+impl Default for LogGroups {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LogGroups {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
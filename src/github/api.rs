@@ -0,0 +1,109 @@
+use crate::error::{Error, Result};
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Default GitHub REST API endpoint
+pub const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Writes a curl config `header` line carrying `value`, quoted so that `"`
+/// and `\` in a token cannot end the string early. Line breaks cannot be
+/// part of a header and are dropped.
+pub fn write_curl_header(out: &mut impl Write, value: &str) -> std::io::Result<()> {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\r' | '\n' => {}
+            c => quoted.push(c),
+        }
+    }
+    writeln!(out, "header = \"{}\"", quoted)
+}
+
+/// Minimal GitHub REST API client built on curl, like the installers.
+/// The token is passed to curl on stdin so it never appears in the process list.
+#[derive(Debug, Clone)]
+pub struct GitHubApi {
+    api_url: String,
+    token: String,
+}
+
+impl GitHubApi {
+    pub fn new(api_url: &str, token: &str) -> Self {
+        GitHubApi {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    /// Client for `GITHUB_API_URL`, falling back to api.github.com
+    pub fn from_env(token: &str) -> Self {
+        let api_url = std::env::var("GITHUB_API_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_GITHUB_API_URL.to_string());
+        GitHubApi::new(&api_url, token)
+    }
+
+    /// Sends a GET request to `path`, e.g. `/repos/owner/repo/labels`
+    pub fn get(&self, path: &str) -> Result<Value> {
+        self.request("GET", path, None)
+    }
+
+    /// Sends a POST request with a JSON body to `path`
+    pub fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        self.request("POST", path, Some(body))
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        let url = format!("{}{}", self.api_url, path);
+        let failed = |msg: String| Error::GitHub(format!("{} {} failed: {}", method, url, msg));
+
+        let payload = body
+            .map(|body| {
+                let mut payload = tempfile::NamedTempFile::new()?;
+                payload.write_all(body.to_string().as_bytes())?;
+                Ok::<_, std::io::Error>(payload)
+            })
+            .transpose()
+            .map_err(|e| failed(format!("failed to write request body: {}", e)))?;
+
+        let mut command = Command::new("curl");
+        command
+            .args(["-fsS", "-X", method, "--config", "-"])
+            .args(["-H", "Accept: application/vnd.github+json"]);
+        if let Some(payload) = &payload {
+            command
+                .args(["-H", "Content-Type: application/json", "--data-binary"])
+                .arg(format!("@{}", payload.path().display()));
+        }
+        let mut child = command
+            .arg(&url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| failed(format!("failed to run curl: {}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            write_curl_header(&mut stdin, &format!("Authorization: Bearer {}", self.token))
+                .map_err(|e| failed(format!("failed to pass token to curl: {}", e)))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| failed(format!("failed to run curl: {}", e)))?;
+        if !output.status.success() {
+            return Err(failed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&output.stdout).map_err(|e| failed(format!("invalid JSON: {}", e)))
+    }
+}
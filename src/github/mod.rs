@@ -1,9 +1,17 @@
 //! GitHub module for iFlow CLI Action
 //!
 //! This module contains all GitHub Actions-specific functionality including:
+//! - Minimal GitHub REST API client
+//! - Workflow annotations and log groups
 //! - GitHub outputs handling
+//! - Review findings published as annotations or pull request reviews
+//! - Native issue triage that applies labels
 //! - Step summary generation
 //! - Summary markdown generation
 
+pub mod annotations;
+pub mod api;
 pub mod outputs;
+pub mod review;
 pub mod summary;
+pub mod triage;
@@ -1,8 +1,76 @@
+use crate::error::{Error, Result};
+use crate::logging;
+use crate::secrets::redact;
 use std::fs;
 use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Where the summary of a local run goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SummaryTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+impl std::str::FromStr for SummaryTarget {
+    type Err = String;
+
+    /// Parses a path, `-` meaning stdout
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim() {
+            "" => Err("the summary path cannot be empty".to_string()),
+            "-" => Ok(SummaryTarget::Stdout),
+            path => Ok(SummaryTarget::File(PathBuf::from(path))),
+        }
+    }
+}
+
+/// Step summaries collected by a local run and where they go
+static LOCAL_SUMMARY: Mutex<Option<(SummaryTarget, String)>> = Mutex::new(None);
+
+/// Collects step summaries in memory instead of writing them to
+/// `GITHUB_STEP_SUMMARY`, until [`flush_local_summary`] writes them to `target`
+pub fn capture_step_summary(target: SummaryTarget) {
+    if let Ok(mut guard) = LOCAL_SUMMARY.lock() {
+        *guard = Some((target, String::new()));
+    }
+}
+
+/// Writes the summaries collected since [`capture_step_summary`] to their
+/// target: the file, or stdout rendered for the terminal
+pub fn flush_local_summary() -> Result<()> {
+    let Some((target, content)) = LOCAL_SUMMARY.lock().ok().and_then(|mut g| g.take()) else {
+        return Ok(());
+    };
+    if content.is_empty() {
+        return Ok(());
+    }
+
+    match target {
+        SummaryTarget::Stdout => {
+            logging::summary(&content);
+            Ok(())
+        }
+        SummaryTarget::File(path) => fs::write(&path, content).map_err(|e| {
+            Error::Output(format!(
+                "failed to write summary to '{}': {}",
+                path.display(),
+                e
+            ))
+        }),
+    }
+}
+
+/// Writes content to GitHub Actions step summary, or collects it in a local run
+pub fn write_step_summary(content: &str) -> Result<()> {
+    if let Ok(mut guard) = LOCAL_SUMMARY.lock()
+        && let Some((_, summary)) = guard.as_mut()
+    {
+        summary.push_str(&redact(content));
+        return Ok(());
+    }
 
-/// Writes content to GitHub Actions step summary
-pub fn write_step_summary(content: &str) -> Result<(), String> {
     // Check if we're in GitHub Actions environment
     if std::env::var("GITHUB_ACTIONS").is_err() {
         // Not in GitHub Actions environment, nothing to do
@@ -23,16 +91,16 @@ pub fn write_step_summary(content: &str) -> Result<(), String> {
         .append(true)
         .create(true)
         .open(&summary_file)
-        .map_err(|e| format!("failed to open step summary file: {}", e))
+        .map_err(|e| Error::Output(format!("failed to open step summary file: {}", e)))
         .and_then(|mut file| {
-            file.write_all(content.as_bytes())
-                .map_err(|e| format!("failed to write to step summary: {}", e))
+            file.write_all(redact(content).as_bytes())
+                .map_err(|e| Error::Output(format!("failed to write to step summary: {}", e)))
         })
 }
 
 /// Writes a key=value pair to GITHUB_OUTPUT (GitHub Actions outputs)
 /// Appends to the file specified by the GITHUB_OUTPUT environment variable.
-pub fn write_github_output(key: &str, value: &str) -> Result<(), String> {
+pub fn write_github_output(key: &str, value: &str) -> Result<()> {
     // Only proceed if running in GitHub Actions
     if std::env::var("GITHUB_ACTIONS").is_err() {
         return Ok(());
@@ -40,26 +108,27 @@ pub fn write_github_output(key: &str, value: &str) -> Result<(), String> {
 
     let output_file = match std::env::var("GITHUB_OUTPUT") {
         Ok(f) => f,
-        Err(_) => return Err("GITHUB_OUTPUT not set".to_string()),
+        Err(_) => return Err(Error::Output("GITHUB_OUTPUT not set".to_string())),
     };
 
     fs::OpenOptions::new()
         .append(true)
         .create(true)
         .open(&output_file)
-        .map_err(|e| format!("failed to open GITHUB_OUTPUT file: {}", e))
+        .map_err(|e| Error::Output(format!("failed to open GITHUB_OUTPUT file: {}", e)))
         .and_then(|mut file| {
+            let value = redact(value);
             // Use the key=value format. If value contains newlines, use the
             // multiline GitHub Actions syntax (key<<EOF\n...\nEOF)
             if value.contains('\n') {
                 // Write as multiline
                 let payload = format!("{}<<EOF\n{}\nEOF\n", key, value);
                 file.write_all(payload.as_bytes())
-                    .map_err(|e| format!("failed to write to GITHUB_OUTPUT: {}", e))
+                    .map_err(|e| Error::Output(format!("failed to write to GITHUB_OUTPUT: {}", e)))
             } else {
                 let payload = format!("{}={}\n", key, value);
                 file.write_all(payload.as_bytes())
-                    .map_err(|e| format!("failed to write to GITHUB_OUTPUT: {}", e))
+                    .map_err(|e| Error::Output(format!("failed to write to GITHUB_OUTPUT: {}", e)))
             }
         })
 }
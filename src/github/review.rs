@@ -0,0 +1,405 @@
+use crate::error::Result;
+use crate::github::annotations::{AnnotationLevel, Location, annotation};
+use crate::github::api::GitHubApi;
+use crate::iflow::structured::fenced_json_blocks;
+use crate::logging;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+
+/// Files GitHub lists per page of a pull request; it lists at most 3000
+const FILES_PER_PAGE: usize = 100;
+const MAX_FILE_PAGES: usize = 30;
+
+/// Lines of each file of a pull request that its diff shows, which are the
+/// only lines a review can comment on
+pub type DiffLines = HashMap<String, Vec<RangeInclusive<u32>>>;
+
+/// How review findings reported by the agent are published
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ReviewMode {
+    /// Findings are not requested
+    #[default]
+    Off,
+    /// Findings become `::error`/`::warning`/`::notice` annotations on the diff
+    Annotations,
+    /// Findings become inline comments of a pull request review
+    PrReview,
+}
+
+/// Instructions appended to the prompt in review mode
+const FINDINGS_INSTRUCTIONS: &str = r#"
+
+When you have finished, end your final message with your review findings as a fenced JSON block in exactly this format:
+
+```json
+{"findings": [{"file": "path/relative/to/the/repository", "line": 42, "severity": "error", "message": "What is wrong and how to fix it"}]}
+```
+
+Use "error", "warning" or "notice" as severity and the line number in the current version of the file. Report an empty list when there are no findings."#;
+
+/// Appends the findings instructions to the prompt
+pub fn review_prompt(prompt: &str) -> String {
+    format!("{}{}", prompt, FINDINGS_INSTRUCTIONS)
+}
+
+/// A review finding reported by the agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub file: String,
+    pub line: u32,
+    pub severity: AnnotationLevel,
+    pub message: String,
+}
+
+impl Finding {
+    fn from_json(value: &Value) -> Option<Finding> {
+        let line = value["line"]
+            .as_u64()
+            .or_else(|| value["line"].as_str()?.parse().ok())?;
+        let severity = match value["severity"]
+            .as_str()
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "error" | "critical" | "high" => AnnotationLevel::Error,
+            "warning" | "medium" => AnnotationLevel::Warning,
+            _ => AnnotationLevel::Notice,
+        };
+
+        Some(Finding {
+            file: value["file"]
+                .as_str()
+                .filter(|f| !f.is_empty())?
+                .to_string(),
+            line: u32::try_from(line).ok().filter(|line| *line > 0)?,
+            severity,
+            message: value["message"]
+                .as_str()
+                .filter(|m| !m.is_empty())?
+                .to_string(),
+        })
+    }
+
+    /// JSON form used by the `findings` output
+    pub fn to_json(&self) -> Value {
+        let severity = match self.severity {
+            AnnotationLevel::Error => "error",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Notice => "notice",
+        };
+        json!({
+            "file": self.file,
+            "line": self.line,
+            "severity": severity,
+            "message": self.message,
+        })
+    }
+}
+
+/// Parses the findings from the last fenced JSON block of the agent's response.
+/// Returns `None` when the response contains no findings block; malformed
+/// entries are skipped.
+pub fn parse_findings(response: &str) -> Option<Vec<Finding>> {
+    let value = fenced_json_blocks(response)
+        .filter(|value| value.is_array() || value["findings"].is_array())
+        .last()?;
+
+    let entries = value.as_array().or_else(|| value["findings"].as_array())?;
+    Some(entries.iter().filter_map(Finding::from_json).collect())
+}
+
+/// Resolves a finding's path, which the prompt asks to be relative to the
+/// repository root, and returns it normalized, or `None` when it escapes the
+/// repository or does not exist
+fn repository_path(file: &str, root: &Path) -> Option<String> {
+    let relative = Path::new(file.trim_start_matches("./"));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+
+    let path = root.join(relative).canonicalize().ok()?;
+    if !path.is_file() {
+        return None;
+    }
+    let path = path.strip_prefix(root.canonicalize().ok()?).ok()?;
+    Some(path.to_string_lossy().replace('\\', "/"))
+}
+
+/// Keeps findings whose file exists in the repository at `root` and whose line
+/// exists in that file, normalizing their paths.
+/// Returns the valid findings and a description of each rejected one.
+pub fn validate_findings(findings: Vec<Finding>, root: &Path) -> (Vec<Finding>, Vec<String>) {
+    let mut valid = Vec::new();
+    let mut rejected = Vec::new();
+
+    for mut finding in findings {
+        let Some(path) = repository_path(&finding.file, root) else {
+            rejected.push(format!(
+                "{}: file not found in the repository",
+                finding.file
+            ));
+            continue;
+        };
+        let lines = std::fs::read_to_string(root.join(&path))
+            .map(|content| content.lines().count())
+            .unwrap_or_default();
+        if finding.line as usize > lines {
+            rejected.push(format!(
+                "{}:{}: line is past the end of the file",
+                finding.file, finding.line
+            ));
+            continue;
+        }
+
+        finding.file = path;
+        valid.push(finding);
+    }
+
+    (valid, rejected)
+}
+
+/// Emits one annotation per finding
+pub fn annotate_findings(findings: &[Finding]) {
+    for finding in findings {
+        let location = Location {
+            file: finding.file.clone(),
+            line: Some(finding.line),
+        };
+        logging::workflow_command(&annotation(
+            finding.severity,
+            &finding.message,
+            Some(&location),
+        ));
+    }
+}
+
+/// Reads the pull request number from the workflow event payload
+pub fn pull_request_number(event: &Value) -> Option<u64> {
+    // Issue comment events on a pull request carry it as an issue
+    event["pull_request"]["number"].as_u64().or_else(|| {
+        let issue = &event["issue"];
+        issue["pull_request"]
+            .is_object()
+            .then(|| issue["number"].as_u64())
+            .flatten()
+    })
+}
+
+/// Body of a pull request review with one inline comment per finding
+pub fn review_payload(findings: &[Finding]) -> Value {
+    let comments: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let label = match finding.severity {
+                AnnotationLevel::Error => "**Error**",
+                AnnotationLevel::Warning => "**Warning**",
+                AnnotationLevel::Notice => "**Note**",
+            };
+            json!({
+                "path": finding.file,
+                "line": finding.line,
+                "side": "RIGHT",
+                "body": format!("{}: {}", label, finding.message),
+            })
+        })
+        .collect();
+
+    json!({
+        "event": "COMMENT",
+        "body": format!("iFlow CLI review: {} finding(s)", findings.len()),
+        "comments": comments,
+    })
+}
+
+/// Creates a pull request review with inline comments via the GitHub API
+pub fn create_pr_review(
+    api: &GitHubApi,
+    repository: &str,
+    number: u64,
+    findings: &[Finding],
+) -> Result<()> {
+    api.post(
+        &format!("/repos/{}/pulls/{}/reviews", repository, number),
+        &review_payload(findings),
+    )?;
+    Ok(())
+}
+
+/// Lines of the new version of a file shown by the hunks of its `patch`
+pub fn hunk_lines(patch: &str) -> Vec<RangeInclusive<u32>> {
+    patch
+        .lines()
+        .filter_map(|line| {
+            // @@ -old_start,old_count +new_start,new_count @@
+            let new = line
+                .strip_prefix("@@ ")?
+                .split_whitespace()
+                .find_map(|range| range.strip_prefix('+'))?;
+            let (start, count) = match new.split_once(',') {
+                Some((start, count)) => (start.parse::<u32>().ok()?, count.parse::<u32>().ok()?),
+                None => (new.parse::<u32>().ok()?, 1),
+            };
+            (count > 0).then(|| start..=start + count - 1)
+        })
+        .collect()
+}
+
+/// Reads the lines shown by the diff of a pull request via the GitHub API
+pub fn pull_request_diff(api: &GitHubApi, repository: &str, number: u64) -> Result<DiffLines> {
+    let mut diff = DiffLines::new();
+    for page in 1..=MAX_FILE_PAGES {
+        let files = api.get(&format!(
+            "/repos/{}/pulls/{}/files?per_page={}&page={}",
+            repository, number, FILES_PER_PAGE, page
+        ))?;
+        let files = files.as_array().cloned().unwrap_or_default();
+        for file in &files {
+            // Binary and very large files have no patch to comment on
+            if let Some(name) = file["filename"].as_str() {
+                diff.insert(
+                    name.to_string(),
+                    hunk_lines(file["patch"].as_str().unwrap_or_default()),
+                );
+            }
+        }
+        if files.len() < FILES_PER_PAGE {
+            break;
+        }
+    }
+    Ok(diff)
+}
+
+/// Splits findings into those on a line shown by the diff and the others
+pub fn split_by_diff(findings: &[Finding], diff: &DiffLines) -> (Vec<Finding>, Vec<Finding>) {
+    findings.iter().cloned().partition(|finding| {
+        diff.get(&finding.file)
+            .is_some_and(|hunks| hunks.iter().any(|hunk| hunk.contains(&finding.line)))
+    })
+}
+
+/// Publishes findings as a review on pull request `number`. Findings outside
+/// its diff, which a review cannot comment on, and all findings when the
+/// review cannot be created are reported as annotations instead. Returns the
+/// number of review comments created.
+pub fn publish_pr_review(
+    api: &GitHubApi,
+    repository: &str,
+    number: u64,
+    findings: &[Finding],
+) -> usize {
+    let diff = match pull_request_diff(api, repository, number) {
+        Ok(diff) => diff,
+        Err(e) => {
+            logging::warn(&format!(
+                "⚠️  Warning: failed to read the pull request diff, reporting findings as annotations: {}",
+                e
+            ));
+            annotate_findings(findings);
+            return 0;
+        }
+    };
+
+    let (inline, outside) = split_by_diff(findings, &diff);
+    if !outside.is_empty() {
+        logging::warn(&format!(
+            "⚠️  {} finding(s) outside the pull request diff, reporting them as annotations",
+            outside.len()
+        ));
+        annotate_findings(&outside);
+    }
+    if inline.is_empty() {
+        return 0;
+    }
+    match create_pr_review(api, repository, number, &inline) {
+        Ok(()) => {
+            logging::info(&format!(
+                "✅ Created a review on pull request #{} with {} comment(s)",
+                number,
+                inline.len()
+            ));
+            inline.len()
+        }
+        Err(e) => {
+            logging::warn(&format!(
+                "⚠️  Warning: failed to create the review, reporting findings as annotations: {}",
+                e
+            ));
+            annotate_findings(&inline);
+            0
+        }
+    }
+}
+
+/// Repository root that finding paths are relative to: the workspace in GitHub
+/// Actions, otherwise the git repository containing the working directory, or
+/// the working directory itself outside a repository
+pub fn repository_root(working_directory: &Path) -> PathBuf {
+    std::env::var("GITHUB_WORKSPACE")
+        .ok()
+        .filter(|workspace| !workspace.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| git_root(working_directory))
+        .unwrap_or_else(|| working_directory.to_path_buf())
+}
+
+/// Nearest directory at or above `dir` that contains a `.git` entry
+pub fn git_root(dir: &Path) -> Option<PathBuf> {
+    let dir = dir.canonicalize().ok()?;
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Parses, validates and publishes the findings in the agent's response
+/// according to `mode`, returning the published findings. Outside a pull
+/// request, `pr-review` falls back to annotations.
+pub fn publish_findings(
+    mode: ReviewMode,
+    response: &str,
+    working_directory: &Path,
+    token: Option<&str>,
+) -> Result<Vec<Finding>> {
+    let Some(findings) = parse_findings(response) else {
+        logging::warn("⚠️  Warning: the agent did not report a findings block");
+        return Ok(Vec::new());
+    };
+
+    let root = repository_root(working_directory);
+    let (findings, rejected) = validate_findings(findings, &root);
+    for reason in rejected {
+        logging::warn(&format!("⚠️  Skipping finding: {}", reason));
+    }
+    logging::info(&format!("🔎 {} review finding(s)", findings.len()));
+
+    let event = std::env::var("GITHUB_EVENT_PATH")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|event| serde_json::from_str::<Value>(&event).ok());
+    let pull_request = event.as_ref().and_then(pull_request_number);
+    let repository = std::env::var("GITHUB_REPOSITORY").ok();
+    let token = token.filter(|t| !t.is_empty());
+
+    match (mode, pull_request, repository, token) {
+        (ReviewMode::Off, ..) => {}
+        (ReviewMode::PrReview, Some(number), Some(repository), Some(token)) => {
+            if !findings.is_empty() {
+                publish_pr_review(&GitHubApi::from_env(token), &repository, number, &findings);
+            }
+        }
+        (ReviewMode::PrReview, ..) => {
+            logging::warn(
+                "⚠️  Warning: not running on a pull request with a token, reporting findings as annotations",
+            );
+            annotate_findings(&findings);
+        }
+        (ReviewMode::Annotations, ..) => annotate_findings(&findings),
+    }
+
+    Ok(findings)
+}
@@ -1,3 +1,4 @@
+use crate::secrets::redact;
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -13,27 +14,61 @@ pub fn generate_summary_markdown(
         .get("isTimeout")
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
-    let timeout_val = config
-        .get("timeout")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(3600) as i32;
-    let model_val = config
-        .get("model")
-        .and_then(|v| v.as_str())
-        .unwrap_or("qwen3-coder-plus");
-    let base_url_val = config
-        .get("baseURL")
-        .and_then(|v| v.as_str())
-        .unwrap_or("https://apis.iflow.cn/v1");
-    let working_dir_val = config
-        .get("workingDir")
-        .and_then(|v| v.as_str())
-        .unwrap_or(".");
+    // Settings missing from `config`, e.g. in a summary rebuilt from a log,
+    // are left out of the summary
+    let timeout_val = config.get("timeout").and_then(|v| v.as_i64());
+    let model_val = config.get("model").and_then(|v| v.as_str());
+    let base_url_val = config.get("baseURL").and_then(|v| v.as_str());
+    let working_dir_val = config.get("workingDir").and_then(|v| v.as_str());
     let prompt_val = config.get("prompt").and_then(|v| v.as_str()).unwrap_or("");
+    let failed_models: Vec<&str> = config
+        .get("failedModels")
+        .and_then(|v| v.as_array())
+        .map(|models| models.iter().filter_map(|m| m.as_str()).collect())
+        .unwrap_or_default();
+    let attempts = config
+        .get("attempts")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let budget_exceeded = config.get("budgetExceeded").and_then(|v| v.as_str());
+    let cancelled = config.get("cancelled").and_then(|v| v.as_str());
+    let stalled = config.get("stalled").and_then(|v| v.as_str());
+    // How the session ended when it did not finish, e.g. `model_error`
+    let outcome = config
+        .get("outcome")
+        .and_then(|v| v.as_str())
+        .filter(|outcome| *outcome != "finished" && *outcome != "timeout");
+    let outcome_title = match outcome {
+        Some("model_error") => "Model Error",
+        Some("disconnected") => "Disconnected",
+        Some("stream_ended") => "Stream Ended",
+        _ => "Failed",
+    };
+    let outcome_detail = config.get("outcomeDetail").and_then(|v| v.as_str());
+    let stop_reason = config.get("stopReason").and_then(|v| v.as_str());
+    let error_details = config.get("errorDetails").filter(|v| !v.is_null());
+    let metrics = config.get("metrics").and_then(|v| v.as_object()).cloned();
+    let environment = config
+        .get("environment")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
 
     // Add header with emoji based on status
-    if is_timeout {
+    if cancelled.is_some() {
+        summary.push_str("## 🚫 iFlow CLI Execution Summary - Cancelled\n\n");
+    } else if stalled.is_some() {
+        summary.push_str("## 💤 iFlow CLI Execution Summary - Stalled\n\n");
+    } else if is_timeout {
         summary.push_str("## ⏰ iFlow CLI Execution Summary - Timeout\n\n");
+    } else if budget_exceeded.is_some() {
+        summary.push_str("## 🛑 iFlow CLI Execution Summary - Budget Exceeded\n\n");
+    } else if outcome.is_some() {
+        summary.push_str(&format!(
+            "## ❌ iFlow CLI Execution Summary - {}\n\n",
+            outcome_title
+        ));
     } else if exit_code == 0 {
         summary.push_str("## ✅ iFlow CLI Execution Summary\n\n");
     } else {
@@ -42,30 +77,203 @@ pub fn generate_summary_markdown(
 
     // Add execution status with more detail
     summary.push_str("### 📊 Status\n\n");
-    if is_timeout {
+    if let Some(cancelled) = cancelled {
+        summary.push_str("🚫 **Execution**: Cancelled\n");
+        summary.push_str(&format!("📡 **Reason**: {}\n", cancelled));
+        summary.push_str(&format!("💥 **Exit Code**: {}\n\n", exit_code));
+    } else if let Some(stalled) = stalled {
+        summary.push_str("💤 **Execution**: Stalled\n");
+        summary.push_str(&format!("⏱️ **Reason**: {}\n", stalled));
+        summary.push_str(&format!("💥 **Exit Code**: {}\n\n", exit_code));
+    } else if is_timeout {
         summary.push_str("⏰ **Execution**: Timed Out\n");
-        summary.push_str(&format!(
-            "🕒 **Timeout Duration**: {} seconds\n",
-            timeout_val
-        ));
+        if let Some(timeout) = timeout_val {
+            summary.push_str(&format!("🕒 **Timeout Duration**: {} seconds\n", timeout));
+        }
+        summary.push_str(&format!("💥 **Exit Code**: {}\n\n", exit_code));
+    } else if let Some(budget_exceeded) = budget_exceeded {
+        summary.push_str("🛑 **Execution**: Budget Exceeded\n");
+        summary.push_str(&format!("📏 **Budget**: {}\n", budget_exceeded));
+        summary.push_str(&format!("💥 **Exit Code**: {}\n\n", exit_code));
+    } else if outcome.is_some() {
+        summary.push_str(&format!("❌ **Execution**: {}\n", outcome_title));
+        if let Some(detail) = outcome_detail {
+            summary.push_str(&format!("💬 **Details**: {}\n", detail));
+        }
         summary.push_str(&format!("💥 **Exit Code**: {}\n\n", exit_code));
     } else if exit_code == 0 {
         summary.push_str("🎉 **Execution**: Successful\n");
         summary.push_str("🎯 **Exit Code**: 0\n\n");
     } else {
         summary.push_str("⚠️ **Execution**: Failed\n");
+        if let Some(detail) = outcome_detail {
+            summary.push_str(&format!("💬 **Details**: {}\n", detail));
+        }
         summary.push_str(&format!("💥 **Exit Code**: {}\n\n", exit_code));
     }
 
+    // Add why the agent stopped and the details of a model error
+    if let Some(stop_reason) = stop_reason {
+        summary.push_str(&format!("🏁 **Stop Reason**: {}\n\n", stop_reason));
+    }
+    if let Some(details) = error_details {
+        summary.push_str("🧾 **Error Details**:\n\n");
+        summary.push_str(&format!(
+            "```json\n{}\n```\n\n",
+            serde_json::to_string_pretty(details).unwrap_or_default()
+        ));
+    }
+
     // Add configuration details in a table format
-    summary.push_str("### ⚙️ Configuration\n\n");
-    summary.push_str("| Setting | Value |\n");
-    summary.push_str("|---------|-------|\n");
-    summary.push_str(&format!("| Model | `{}` |\n", model_val));
-    summary.push_str(&format!("| Base URL | `{}` |\n", base_url_val));
-    summary.push_str(&format!("| Timeout | {} seconds |\n", timeout_val));
-    summary.push_str(&format!("| Working Directory | `{}` |\n", working_dir_val));
-    summary.push('\n');
+    let settings: Vec<(&str, String)> = [
+        ("Model", model_val.map(|v| format!("`{}`", v))),
+        ("Base URL", base_url_val.map(|v| format!("`{}`", v))),
+        ("Timeout", timeout_val.map(|v| format!("{} seconds", v))),
+        (
+            "Working Directory",
+            working_dir_val.map(|v| format!("`{}`", v)),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| Some((label, value?)))
+    .collect();
+    if !settings.is_empty() {
+        summary.push_str("### ⚙️ Configuration\n\n");
+        summary.push_str("| Setting | Value |\n");
+        summary.push_str("|---------|-------|\n");
+        for (label, value) in settings {
+            summary.push_str(&format!("| {} | {} |\n", label, value));
+        }
+        summary.push('\n');
+    }
+
+    // Add environment report for correlating runs
+    if !environment.is_empty() {
+        summary.push_str("### 🧭 Environment\n\n");
+        summary.push_str("| Component | Value |\n");
+        summary.push_str("|-----------|-------|\n");
+        for (label, key) in [
+            ("Action", "action_version"),
+            ("iFlow CLI", "iflow_version"),
+            ("GitHub CLI", "gh_version"),
+            ("Node.js", "node_version"),
+            ("iFlow SDK", "sdk_version"),
+            ("OS", "os"),
+            ("Architecture", "arch"),
+            ("Base URL Host", "base_url_host"),
+        ] {
+            let value = environment
+                .get(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            summary.push_str(&format!("| {} | `{}` |\n", label, value));
+        }
+        summary.push('\n');
+    }
+
+    // Add model fallback details when the configured model did not produce the result
+    if let (false, Some(model)) = (failed_models.is_empty(), model_val) {
+        summary.push_str("### 🔁 Model Fallback\n\n");
+        summary.push_str(&format!(
+            "Result produced by `{}` after these models failed:\n\n",
+            model
+        ));
+        for failed in &failed_models {
+            summary.push_str(&format!("- {}\n", failed));
+        }
+        summary.push('\n');
+    }
+
+    // List every failed attempt that preceded the final result
+    if !attempts.is_empty() {
+        summary.push_str("### 🔄 Attempts\n\n");
+        summary.push_str("| # | Model | Error | Action |\n");
+        summary.push_str("|---|-------|-------|--------|\n");
+        for (i, attempt) in attempts.iter().enumerate() {
+            let field = |key: &str| {
+                attempt
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .replace('|', "\\|")
+                    .replace('\n', " ")
+            };
+            summary.push_str(&format!(
+                "| {} | `{}` | {} | {} |\n",
+                i + 1,
+                field("model"),
+                field("error"),
+                field("action")
+            ));
+        }
+        summary.push('\n');
+    }
+
+    // Add timing, usage and cost metrics
+    if let Some(metrics) = metrics {
+        let millis = |key: &str| metrics.get(key).and_then(|v| v.as_u64());
+        let seconds = |ms: Option<u64>| {
+            ms.map(|ms| format!("{:.2} s", ms as f64 / 1000.0))
+                .unwrap_or_else(|| "n/a".to_string())
+        };
+        let tokens = |key: &str| {
+            metrics
+                .get(key)
+                .and_then(|v| v.as_u64())
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "not reported".to_string())
+        };
+        let tool_calls = metrics
+            .get("toolCalls")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        summary.push_str("### ⏱️ Metrics\n\n");
+        summary.push_str("| Metric | Value |\n");
+        summary.push_str("|--------|-------|\n");
+        summary.push_str(&format!(
+            "| Connect Time | {} |\n",
+            seconds(millis("connectMs"))
+        ));
+        summary.push_str(&format!(
+            "| Time to First Token | {} |\n",
+            seconds(millis("firstTokenMs"))
+        ));
+        summary.push_str(&format!(
+            "| Session Duration | {} |\n",
+            seconds(millis("durationMs"))
+        ));
+        summary.push_str(&format!("| Tool Calls | {} |\n", tool_calls.len()));
+        summary.push_str(&format!(
+            "| Prompt Tokens (estimated) | {} |\n",
+            tokens("inputTokens")
+        ));
+        summary.push_str(&format!(
+            "| Output Tokens (estimated) | {} |\n",
+            tokens("outputTokens")
+        ));
+        if let Some(cost) = metrics.get("estimatedCostUsd").and_then(|v| v.as_f64()) {
+            summary.push_str(&format!("| Estimated Cost | ${:.4} |\n", cost));
+        }
+        summary.push('\n');
+
+        if !tool_calls.is_empty() {
+            summary.push_str("| Tool Call | Duration |\n");
+            summary.push_str("|-----------|----------|\n");
+            for tool_call in &tool_calls {
+                let name = tool_call
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .replace('|', "\\|")
+                    .replace('\n', " ");
+                let duration = tool_call.get("durationMs").and_then(|v| v.as_u64());
+                summary.push_str(&format!("| {} | {} |\n", name, seconds(duration)));
+            }
+            summary.push('\n');
+        }
+    }
 
     // Add prompt section
     summary.push_str("### 📝 Input Prompt\n\n");
@@ -110,10 +318,9 @@ pub fn generate_summary_markdown(
         // Add troubleshooting hints for common errors
         if is_timeout {
             summary.push_str("#### ⏰ Timeout Information\n\n");
-            summary.push_str(&format!(
-                "- **Configured Timeout**: {} seconds\n",
-                timeout_val
-            ));
+            if let Some(timeout) = timeout_val {
+                summary.push_str(&format!("- **Configured Timeout**: {} seconds\n", timeout));
+            }
             summary.push_str("- **Reason**: The iFlow CLI command did not complete within the specified timeout period\n");
             summary.push_str("- **Exit Code**: 124 (timeout)\n\n");
 
@@ -142,7 +349,98 @@ pub fn generate_summary_markdown(
         "*🤖 Generated by [iFlow CLI Action](https://github.com/iflow-ai/iflow-cli-action)*\n\n",
     );
 
-    summary
+    // Never leak registered secrets through the step summary or outputs
+    redact(&summary)
+}
+
+/// A session recovered from a saved log, see [`parse_transcript`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    /// Messages in the form collected while the session ran
+    pub messages: String,
+    /// Exit code of the failure that ended the run, 0 when it succeeded
+    pub exit_code: i32,
+    /// Summary settings recovered from the log, e.g. `stopReason` or `cancelled`
+    pub config: HashMap<&'static str, Value>,
+}
+
+/// Rebuilds a session from a log written with `log_format: json`; any other
+/// log is taken as the messages of a successful run as it is
+pub fn parse_transcript(log: &str) -> Transcript {
+    let lines: Option<Vec<Value>> = log
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).ok().filter(Value::is_object))
+        .collect();
+    let Some(lines) = lines.filter(|lines| !lines.is_empty()) else {
+        return Transcript {
+            messages: log.trim().to_string(),
+            ..Transcript::default()
+        };
+    };
+
+    let mut transcript = Transcript::default();
+    for line in lines {
+        let message = line["message"].as_str().unwrap_or_default();
+        match line["event"].as_str().unwrap_or_default() {
+            "assistant" => {
+                transcript
+                    .messages
+                    .push_str(&format!("\n🤖 Assistant: {}", message));
+            }
+            "tool_call" => transcript.messages.push_str(&format!("🔧 {}", message)),
+            "plan" => transcript.messages.push_str(&format!("\n📋 {}", message)),
+            "user" => transcript.messages.push_str(&format!("\n👤 {}", message)),
+            "model_error" => transcript.messages.push_str(&format!("\n❌ {}", message)),
+            "task_finish" => {
+                transcript.messages.push_str(&format!("\n{}", message));
+                if let Some(stop_reason) = line["stopReason"].as_str() {
+                    transcript.config.insert("stopReason", stop_reason.into());
+                }
+            }
+            "failure" => {
+                transcript.exit_code = line["exitCode"].as_i64().unwrap_or(1) as i32;
+                let key = match line["kind"].as_str().unwrap_or_default() {
+                    "cancelled" => "cancelled",
+                    "stalled" => "stalled",
+                    "budget" => "budgetExceeded",
+                    "timeout" => {
+                        transcript.config.insert("isTimeout", true.into());
+                        continue;
+                    }
+                    "model" => {
+                        transcript.config.insert("outcome", "model_error".into());
+                        "outcomeDetail"
+                    }
+                    "connection" => {
+                        transcript.config.insert("outcome", "disconnected".into());
+                        "outcomeDetail"
+                    }
+                    _ => continue,
+                };
+                transcript.config.insert(key, message.into());
+            }
+            _ => {}
+        }
+    }
+    transcript
+}
+
+/// Generates the step summary section of a triage run
+pub fn generate_triage_markdown(issue_number: u64, labels: &[String], rationale: &str) -> String {
+    let mut summary = format!("### 🏷️ Triage of Issue #{}\n\n", issue_number);
+    if labels.is_empty() {
+        summary.push_str("**Applied Labels**: none\n\n");
+    } else {
+        let labels: Vec<String> = labels.iter().map(|l| format!("`{}`", l)).collect();
+        summary.push_str(&format!("**Applied Labels**: {}\n\n", labels.join(", ")));
+    }
+    for line in rationale.lines() {
+        summary.push_str(&format!("> {}\n", line));
+    }
+    summary.push('\n');
+
+    redact(&summary)
 }
 
 /// Detects if text looks like code
@@ -0,0 +1,254 @@
+use crate::error::{Error, Result};
+use crate::github::api::GitHubApi;
+use crate::iflow::structured::OutputSchema;
+use serde_json::{Value, json};
+
+/// Labels requested per page when listing the repository's labels; at most
+/// `MAX_LABEL_PAGES` pages are fetched, i.e. 1000 labels
+const LABELS_PER_PAGE: usize = 100;
+const MAX_LABEL_PAGES: usize = 10;
+
+/// Issue being triaged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    /// Labels already applied to the issue
+    pub labels: Vec<String>,
+}
+
+/// Repository label offered to the model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub name: String,
+    pub description: String,
+}
+
+/// Labels chosen by the model and its explanation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriageDecision {
+    pub labels: Vec<String>,
+    pub rationale: String,
+}
+
+/// Reads the issue number from the workflow event payload, including the
+/// `issue_number` input of a `workflow_dispatch` event
+pub fn issue_number(event: &Value) -> Option<u64> {
+    event["issue"]["number"].as_u64().or_else(|| {
+        let input = &event["inputs"]["issue_number"];
+        input
+            .as_u64()
+            .or_else(|| input.as_str()?.trim().parse().ok())
+    })
+}
+
+/// Fetches the title, body and current labels of an issue
+pub fn fetch_issue(api: &GitHubApi, repository: &str, number: u64) -> Result<Issue> {
+    let issue = api.get(&format!("/repos/{}/issues/{}", repository, number))?;
+    let title = issue["title"]
+        .as_str()
+        .ok_or_else(|| Error::GitHub(format!("issue #{} has no title", number)))?;
+
+    Ok(Issue {
+        number,
+        title: title.to_string(),
+        body: issue["body"].as_str().unwrap_or_default().to_string(),
+        labels: label_names(&issue["labels"]),
+    })
+}
+
+/// Fetches the labels of the repository, up to `MAX_LABEL_PAGES` pages
+pub fn fetch_labels(api: &GitHubApi, repository: &str) -> Result<Vec<Label>> {
+    let mut labels = Vec::new();
+    for page in 1..=MAX_LABEL_PAGES {
+        let response = api.get(&format!(
+            "/repos/{}/labels?per_page={}&page={}",
+            repository, LABELS_PER_PAGE, page
+        ))?;
+        let entries = response.as_array().cloned().unwrap_or_default();
+        labels.extend(entries.iter().filter_map(|label| {
+            Some(Label {
+                name: label["name"].as_str()?.to_string(),
+                description: label["description"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+        }));
+        if entries.len() < LABELS_PER_PAGE {
+            break;
+        }
+    }
+    Ok(labels)
+}
+
+/// Adds labels to an issue and returns the labels the issue now has
+pub fn apply_labels(
+    api: &GitHubApi,
+    repository: &str,
+    number: u64,
+    labels: &[String],
+) -> Result<Vec<String>> {
+    let response = api.post(
+        &format!("/repos/{}/issues/{}/labels", repository, number),
+        &json!({ "labels": labels }),
+    )?;
+    Ok(label_names(&response))
+}
+
+fn label_names(labels: &Value) -> Vec<String> {
+    labels
+        .as_array()
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label["name"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Schema of the model's answer; labels are constrained to the repository's labels
+pub fn triage_schema(labels: &[Label]) -> Result<OutputSchema> {
+    let names: Vec<&str> = labels.iter().map(|label| label.name.as_str()).collect();
+    OutputSchema::from_value(json!({
+        "type": "object",
+        "properties": {
+            "labels": {
+                "type": "array",
+                "items": { "enum": names },
+                "uniqueItems": true,
+            },
+            "rationale": { "type": "string", "minLength": 1 },
+        },
+        "required": ["labels", "rationale"],
+    }))
+    .map_err(|e| Error::GitHub(format!("failed to build the triage schema: {}", e)))
+}
+
+/// Prompt asking the model to choose labels for the issue; `guidelines`
+/// (the `prompt` input, if any) are added as repository-specific rules
+pub fn triage_prompt(issue: &Issue, labels: &[Label], guidelines: Option<&str>) -> String {
+    let mut prompt = String::from(
+        "You are an issue triage assistant. Choose the labels that best describe the GitHub issue below, using only labels from the list of available labels. Classify the issue by kind and priority when such labels exist. Do not run commands that modify the issue; the labels are applied for you.\n\n",
+    );
+
+    prompt.push_str("## Available labels\n\n");
+    for label in labels {
+        if label.description.is_empty() {
+            prompt.push_str(&format!("- `{}`\n", label.name));
+        } else {
+            prompt.push_str(&format!("- `{}`: {}\n", label.name, label.description));
+        }
+    }
+
+    prompt.push_str(&format!(
+        "\n## Issue #{}: {}\n\n",
+        issue.number, issue.title
+    ));
+    prompt.push_str(if issue.body.trim().is_empty() {
+        "(no description)"
+    } else {
+        issue.body.trim()
+    });
+    prompt.push('\n');
+    if !issue.labels.is_empty() {
+        prompt.push_str(&format!("\nCurrent labels: {}\n", issue.labels.join(", ")));
+    }
+
+    if let Some(guidelines) = guidelines.filter(|g| !g.trim().is_empty()) {
+        prompt.push_str(&format!("\n## Guidelines\n\n{}\n", guidelines.trim()));
+    }
+
+    prompt.push_str("\nAnswer with the chosen labels and a short rationale explaining the choice.");
+    prompt
+}
+
+/// Reads the model's validated answer
+pub fn parse_decision(answer: &Value) -> Option<TriageDecision> {
+    Some(TriageDecision {
+        labels: answer["labels"]
+            .as_array()?
+            .iter()
+            .filter_map(|label| label.as_str().map(str::to_string))
+            .collect(),
+        rationale: answer["rationale"].as_str()?.to_string(),
+    })
+}
+
+/// Issue triage run natively by the action: the issue and the label list are
+/// fetched up front, and the labels chosen by the model are applied afterwards
+#[derive(Debug, Clone)]
+pub struct Triage {
+    api: GitHubApi,
+    repository: String,
+    pub issue: Issue,
+    pub labels: Vec<Label>,
+}
+
+impl Triage {
+    /// Fetches the issue (from `issue_number` or the workflow event) and the
+    /// repository's labels
+    pub fn prepare(token: &str, issue_number: Option<u64>) -> Result<Triage> {
+        let repository = std::env::var("GITHUB_REPOSITORY")
+            .ok()
+            .filter(|repository| !repository.is_empty())
+            .ok_or_else(|| Error::GitHub("triage requires GITHUB_REPOSITORY".to_string()))?;
+        let number = issue_number
+            .or_else(|| {
+                let event = std::fs::read_to_string(std::env::var("GITHUB_EVENT_PATH").ok()?);
+                self::issue_number(&serde_json::from_str(&event.ok()?).ok()?)
+            })
+            .ok_or_else(|| {
+                Error::GitHub("triage requires an issue_number input or an issue event".to_string())
+            })?;
+
+        let api = GitHubApi::from_env(token);
+        let issue = fetch_issue(&api, &repository, number)?;
+        let labels = fetch_labels(&api, &repository)?;
+        if labels.is_empty() {
+            return Err(Error::GitHub(format!(
+                "{} has no labels to choose from",
+                repository
+            )));
+        }
+
+        Ok(Triage {
+            api,
+            repository,
+            issue,
+            labels,
+        })
+    }
+
+    /// Prompt for the model, see [`triage_prompt`]
+    pub fn prompt(&self, guidelines: Option<&str>) -> String {
+        triage_prompt(&self.issue, &self.labels, guidelines)
+    }
+
+    /// Schema of the model's answer, see [`triage_schema`]
+    pub fn schema(&self) -> Result<OutputSchema> {
+        triage_schema(&self.labels)
+    }
+
+    /// Applies the labels chosen in the model's validated answer and returns
+    /// the decision with the labels that were applied
+    pub fn apply(&self, answer: &Value) -> Result<TriageDecision> {
+        let mut decision = parse_decision(answer)
+            .ok_or_else(|| Error::Schema("the triage answer has no labels".to_string()))?;
+        decision
+            .labels
+            .retain(|label| self.labels.iter().any(|l| &l.name == label));
+
+        if !decision.labels.is_empty() {
+            apply_labels(
+                &self.api,
+                &self.repository,
+                self.issue.number,
+                &decision.labels,
+            )?;
+        }
+        Ok(decision)
+    }
+}
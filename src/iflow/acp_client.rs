@@ -1,61 +1,550 @@
+use crate::cancel;
+use crate::error::{Error, Result};
+use crate::github::annotations::{AnnotationLevel, Location, LogGroups, annotate};
+use crate::iflow::budget::{BudgetTracker, SessionBudget, estimate_tokens};
+use crate::iflow::metrics::{MetricsRecorder, ModelPrice, SessionMetrics};
+use crate::iflow::retry::RetryPolicy;
+use crate::iflow::stop_reason::StopReason;
+use crate::iflow::structured::OutputSchema;
+use crate::iflow::watchdog::{self, Activity, WatchdogSettings, format_duration};
+use crate::logging::{self, Level};
+use crate::telemetry::{self, SessionSpans};
 use futures::stream::StreamExt;
 use iflow_cli_sdk_rust::error::IFlowError;
 use iflow_cli_sdk_rust::{IFlowClient, IFlowOptions, Message};
-use std::io::Write;
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use tracing::Instrument;
+
+/// Port the auto-started iFlow CLI listens on; the next free port is used
+/// when it is taken
+pub const IFLOW_PORT: u16 = 8090;
 
 /// Parameters for communicating with iFlow CLI via ACP client
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct AcpClientParams<'a> {
     pub prompt: &'a str,
     pub base_url: &'a str,
-    pub model: &'a str,
+    /// Model to use, followed by the fallback models
+    pub models: &'a [String],
     pub working_directory: &'a str,
     pub timeout: u32,
     pub debug: bool,
+    /// Error codes reported by the model that abort the session so a fallback model can be tried
+    pub fallback_error_codes: &'a [i32],
+    /// Retry policy for transient connection and API failures
+    pub retry: &'a RetryPolicy,
+    /// Environment report embedded in the summary
+    pub environment: &'a serde_json::Value,
+    /// Per-model prices used to estimate the cost of the session
+    pub model_prices: &'a HashMap<String, ModelPrice>,
+    /// Turn, tool call and token limits; exceeding one cancels the session
+    pub budget: SessionBudget,
+    /// Schema the final answer must match; invalid answers are sent back for correction
+    pub output_schema: Option<&'a OutputSchema>,
+    /// Number of correction prompts before an invalid answer fails the session
+    pub schema_retries: u32,
+    /// Idle timeout, retry prompt and heartbeats while waiting for iFlow
+    pub watchdog: WatchdogSettings<'a>,
+    /// Stop reasons that fail the session, e.g. max tokens
+    pub fail_on_stop_reason: &'a [StopReason],
+    /// Called with every message received from iFlow
+    pub on_message: Option<&'a MessageHandler>,
+}
+
+/// Callback receiving every message of a session, in order
+#[derive(Clone)]
+pub struct MessageHandler(Rc<dyn Fn(&Message)>);
+
+impl MessageHandler {
+    pub fn new(handler: impl Fn(&Message) + 'static) -> Self {
+        MessageHandler(Rc::new(handler))
+    }
+
+    pub fn call(&self, message: &Message) {
+        (self.0)(message)
+    }
+}
+
+impl std::fmt::Debug for MessageHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MessageHandler")
+    }
 }
 
-/// Communicate with iFlow CLI via ACP client
-/// Returns Ok(Some(summary)) when a summary was generated, Ok(None) when nothing to summarize,
-/// or Err(...) on error.
+/// Result of a successful session
+#[derive(Debug, Clone, Default)]
+pub struct SessionOutput {
+    /// Generated summary, `None` when there was nothing to summarize
+    pub summary: Option<String>,
+    pub metrics: SessionMetrics,
+    /// Everything the assistant wrote during the session
+    pub response: String,
+    /// Answer validated against the output schema
+    pub structured_result: Option<serde_json::Value>,
+    /// Why the agent stopped working on the last prompt
+    pub stop_reason: Option<StopReason>,
+}
+
+/// A session that ended, successfully or not
+struct SessionRun {
+    /// What the session produced; empty when it did not connect
+    output: SessionOutput,
+    /// Messages in the form shown in the summary
+    messages: String,
+    /// How the session ended, `None` when it did not connect
+    outcome: Option<SessionOutcome>,
+    /// Error that failed the session
+    error: Option<Error>,
+}
+
+impl SessionRun {
+    /// A session that failed before it produced anything
+    fn failed(error: Error) -> Self {
+        SessionRun {
+            output: SessionOutput::default(),
+            messages: String::new(),
+            outcome: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// What the message task collected while a session ran
+struct CollectedSession {
+    /// Response, metrics and stop reason; the summary and structured result
+    /// are added once the prompts finished
+    output: SessionOutput,
+    /// Messages in the form shown in the summary
+    messages: String,
+    outcome: SessionOutcome,
+    budget_error: Option<Error>,
+}
+
+/// How a session ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionOutcome {
+    /// Every prompt finished
+    Finished,
+    /// The model reported an error
+    ModelError {
+        code: i32,
+        message: String,
+        /// Extra fields of the error, e.g. the file it refers to
+        details: Option<serde_json::Value>,
+    },
+    /// A prompt did not finish within the timeout
+    Timeout,
+    /// The connection to iFlow was lost while a prompt was running
+    Disconnected(String),
+    /// The message stream ended before the prompt finished
+    StreamEnded,
+}
+
+impl SessionOutcome {
+    /// Name used in the summary, e.g. `model_error`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionOutcome::Finished => "finished",
+            SessionOutcome::ModelError { .. } => "model_error",
+            SessionOutcome::Timeout => "timeout",
+            SessionOutcome::Disconnected(_) => "disconnected",
+            SessionOutcome::StreamEnded => "stream_ended",
+        }
+    }
+
+    /// Error that fails the run, `None` when the session finished
+    pub fn error(&self, timeout: u32) -> Option<Error> {
+        match self {
+            SessionOutcome::Finished => None,
+            SessionOutcome::ModelError { code, message, .. } => Some(Error::Model {
+                code: *code,
+                message: message.clone(),
+            }),
+            SessionOutcome::Timeout => Some(Error::Timeout(format!(
+                "the prompt did not finish within {} seconds",
+                timeout
+            ))),
+            SessionOutcome::Disconnected(reason) => Some(Error::Connection(format!(
+                "connection to iFlow lost: {}",
+                reason
+            ))),
+            SessionOutcome::StreamEnded => Some(Error::Session(
+                "the message stream ended before the prompt finished".to_string(),
+            )),
+        }
+    }
+}
+
+/// A failed session attempt, listed in the summary
+#[derive(Debug, Clone)]
+pub struct SessionAttempt {
+    /// Model used for the attempt
+    pub model: String,
+    /// Error that ended the attempt
+    pub error: String,
+    /// What happened next, e.g. "retried after 2.0s" or "fell back to kimi-k2"
+    pub action: String,
+}
+
+/// Classifies an SDK error from connecting to iFlow
+fn connect_error(err: IFlowError) -> Error {
+    match err {
+        IFlowError::Authentication(msg) => Error::Authentication(msg),
+        other => Error::Connection(format!("Failed to connect: {}", other)),
+    }
+}
+
+/// Classifies an SDK error from sending the prompt
+fn send_error(err: IFlowError) -> Error {
+    match err {
+        IFlowError::Authentication(msg) => Error::Authentication(msg),
+        IFlowError::Connection(msg)
+        | IFlowError::Transport(msg)
+        | IFlowError::WebSocket(msg)
+        | IFlowError::ProcessManager(msg) => Error::Connection(msg),
+        IFlowError::Protocol(msg) => match extract_error_code(&msg) {
+            Some(code) => Error::Model { code, message: msg },
+            None => Error::Session(msg),
+        },
+        other => Error::Session(other.to_string()),
+    }
+}
+
+/// Extracts the JSON-RPC error code from a protocol error message such as
+/// `Prompt failed: Object {"code": Number(429), ...}`
+fn extract_error_code(msg: &str) -> Option<i32> {
+    let rest = &msg[msg.find("\"code\"")? + "\"code\"".len()..];
+    let start = rest.find(|c: char| c.is_ascii_digit() || c == '-')?;
+    let digits: String = rest[start..]
+        .chars()
+        .enumerate()
+        .take_while(|(i, c)| c.is_ascii_digit() || (*i == 0 && *c == '-'))
+        .map(|(_, c)| c)
+        .collect();
+    digits.parse().ok()
+}
+
+/// What follows a failed session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NextAttempt {
+    /// The session is retried with the same model
+    Retry,
+    /// The session is retried with the next model
+    Fallback,
+    /// The failure ends the run, e.g. "retries exhausted"
+    Stop(&'static str),
+}
+
+/// Decides what follows each failed session of a run: a retry, a fallback
+/// model or nothing
+#[derive(Debug, Clone)]
+pub struct AttemptPlan<'a> {
+    retry: &'a RetryPolicy,
+    fallback_error_codes: &'a [i32],
+    retry_stalled: bool,
+    models: usize,
+    model: usize,
+    retries: u32,
+    stalled: bool,
+}
+
+impl<'a> AttemptPlan<'a> {
+    /// Plan for `models` models; `retry_stalled` is whether a stalled session
+    /// is retried with the retry prompt
+    pub fn new(
+        retry: &'a RetryPolicy,
+        fallback_error_codes: &'a [i32],
+        retry_stalled: bool,
+        models: usize,
+    ) -> Self {
+        AttemptPlan {
+            retry,
+            fallback_error_codes,
+            retry_stalled,
+            models,
+            model: 0,
+            retries: 0,
+            stalled: false,
+        }
+    }
+
+    /// Index of the model of the next session
+    pub fn model(&self) -> usize {
+        self.model
+    }
+
+    /// Retries of the current model so far
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Whether the next session retries a stalled one
+    pub fn retries_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Decides what follows a session failing with `err` and moves on to it
+    pub fn next(&mut self, err: &Error) -> NextAttempt {
+        let stalled = matches!(err, Error::Stalled(_));
+        let retryable =
+            self.retry.is_retryable(err.retry_class()) || (stalled && self.retry_stalled);
+        if retryable && self.retries < self.retry.max_retries {
+            self.retries += 1;
+            self.stalled = stalled;
+            NextAttempt::Retry
+        } else if self.model + 1 < self.models
+            && err.is_fallback_eligible(self.fallback_error_codes)
+        {
+            self.model += 1;
+            self.retries = 0;
+            self.stalled = false;
+            NextAttempt::Fallback
+        } else if retryable {
+            NextAttempt::Stop("retries exhausted")
+        } else {
+            NextAttempt::Stop("not retryable")
+        }
+    }
+}
+
+/// Communicate with iFlow CLI via ACP client, retrying transient failures according to
+/// `params.retry` and falling back to the next model; `switch_model` configures iFlow
+/// for a fallback model. The session that ends the run is summarized along with
+/// every failed attempt before it, whether it succeeded or not.
+/// Returns the summary and metrics of the successful session, or Err(...) on error,
+/// together with the failed attempts.
 pub async fn communicate_with_iflow_cli_via_acp(
     params: AcpClientParams<'_>,
-) -> Result<Option<String>, String> {
-    let AcpClientParams {
-        prompt,
-        base_url,
-        model,
-        working_directory,
-        timeout,
-        debug,
-    } = params;
+    switch_model: &mut dyn FnMut(&str) -> Result<()>,
+) -> (Result<SessionOutput>, Vec<SessionAttempt>) {
+    let mut attempts = Vec::new();
+    let mut plan = AttemptPlan::new(
+        params.retry,
+        params.fallback_error_codes,
+        params.watchdog.retry_prompt.is_some(),
+        params.models.len(),
+    );
+    let (model, mut run) = loop {
+        let model = &params.models[plan.model()];
+        // A stalled session is retried with the retry prompt after the prompt
+        // (see `WatchdogSettings::stalled_retry_prompt`)
+        let prompt = match params.watchdog.stalled_retry_prompt(params.prompt) {
+            Some(retry_prompt) if plan.retries_stalled() => retry_prompt,
+            _ => params.prompt.to_string(),
+        };
+        let mut run = run_session(params, model, &prompt).await;
+        let Some(err) = &run.error else {
+            break (model, run);
+        };
+
+        let next = plan.next(err);
+        let action = match next {
+            NextAttempt::Retry => {
+                let delay = params.retry.delay(plan.retries());
+                logging::warn(&format!(
+                    "🔄 Attempt {} failed: {}. Retrying in {:.1}s ({}/{})",
+                    attempts.len() + 1,
+                    err,
+                    delay.as_secs_f64(),
+                    plan.retries(),
+                    params.retry.max_retries
+                ));
+                annotate(
+                    AnnotationLevel::Warning,
+                    &format!("Attempt {} failed, retrying: {}", attempts.len() + 1, err),
+                    None,
+                );
+                tokio::time::sleep(delay).await;
+                if plan.retries_stalled() {
+                    format!(
+                        "retried after {:.1}s with the retry prompt",
+                        delay.as_secs_f64()
+                    )
+                } else {
+                    format!("retried after {:.1}s", delay.as_secs_f64())
+                }
+            }
+            NextAttempt::Fallback => {
+                let fallback = &params.models[plan.model()];
+                logging::warn(&format!("⚠️  Model {} failed: {}", model, err));
+                logging::info(&format!("🔁 Falling back to model: {}", fallback));
+                format!("fell back to {}", fallback)
+            }
+            NextAttempt::Stop(reason) => reason.to_string(),
+        };
+        attempts.push(SessionAttempt {
+            model: model.to_string(),
+            error: err.to_string(),
+            action,
+        });
+        match next {
+            NextAttempt::Stop(_) => break (model, run),
+            NextAttempt::Fallback => {
+                if let Err(e) = switch_model(&params.models[plan.model()]) {
+                    run.error = Some(e);
+                    break (model, run);
+                }
+            }
+            NextAttempt::Retry => {}
+        }
+    };
+
+    let summary = write_session_summary(&params, model, &run, &attempts);
+    let result = match run.error {
+        None => {
+            run.output.summary = Some(summary);
+            Ok(run.output)
+        }
+        Some(err) => Err(err),
+    };
+    (result, attempts)
+}
+
+/// Generates the summary of the session that ended the run and writes it to the
+/// GitHub step summary; `attempts` lists every failed attempt
+fn write_session_summary(
+    params: &AcpClientParams<'_>,
+    model: &str,
+    run: &SessionRun,
+    attempts: &[SessionAttempt],
+) -> String {
     use crate::github::outputs::write_step_summary;
     use crate::github::summary::generate_summary_markdown;
 
-    // Holder to pass the generated summary out of the LocalSet closure
-    let summary_holder = Arc::new(Mutex::new(None::<String>));
+    let exit_code = run.error.as_ref().map_or(0, Error::exit_code);
+    let attempts_json: Vec<serde_json::Value> = attempts
+        .iter()
+        .map(|a| serde_json::json!({ "model": a.model, "error": a.error, "action": a.action }))
+        .collect();
+    let failed_models: Vec<String> = attempts
+        .iter()
+        .filter(|a| a.action.starts_with("fell back"))
+        .map(|a| format!("{}: {}", a.model, a.error))
+        .collect();
 
-    // Initialize logging with environment variable support
-    let log_level = if debug || std::env::var("ACTIONS_STEP_DEBUG").is_ok() {
-        tracing::Level::DEBUG
-    } else {
-        tracing::Level::INFO
+    // Prepare configuration map for summary generation
+    let mut config_map = std::collections::HashMap::new();
+    if let Some(outcome) = &run.outcome {
+        config_map.insert(
+            "isTimeout",
+            serde_json::Value::Bool(*outcome == SessionOutcome::Timeout),
+        );
+        config_map.insert("outcome", serde_json::json!(outcome.as_str()));
+        if let SessionOutcome::ModelError {
+            details: Some(details),
+            ..
+        } = outcome
+        {
+            config_map.insert("errorDetails", details.clone());
+        }
+        if let Some(err) = outcome.error(params.timeout) {
+            config_map.insert("outcomeDetail", serde_json::json!(err.to_string()));
+        }
+    }
+    if let Some(stop_reason) = run.output.stop_reason {
+        config_map.insert("stopReason", serde_json::json!(stop_reason.as_str()));
+    }
+    config_map.insert(
+        "timeout",
+        serde_json::Value::Number(serde_json::Number::from(params.timeout)),
+    );
+    config_map.insert("model", serde_json::json!(model));
+    config_map.insert("baseURL", serde_json::json!(params.base_url));
+    config_map.insert(
+        "workingDir",
+        serde_json::Value::String(params.working_directory.to_string()),
+    );
+    config_map.insert("prompt", serde_json::json!(params.prompt));
+    config_map.insert("failedModels", serde_json::json!(failed_models));
+    config_map.insert("attempts", serde_json::Value::Array(attempts_json));
+    config_map.insert("environment", params.environment.clone());
+    config_map.insert(
+        "metrics",
+        run.output.metrics.to_json(params.model_prices.get(model)),
+    );
+    match &run.error {
+        Some(Error::Budget(exceeded)) => {
+            config_map.insert("budgetExceeded", serde_json::json!(exceeded));
+        }
+        Some(Error::Cancelled(reason)) => {
+            config_map.insert("cancelled", serde_json::json!(reason));
+        }
+        Some(Error::Stalled(reason)) => {
+            config_map.insert("stalled", serde_json::json!(reason));
+        }
+        // Errors without an outcome of their own, e.g. a failed connection
+        Some(err)
+            if run
+                .outcome
+                .as_ref()
+                .and_then(|o| o.error(params.timeout))
+                .is_none() =>
+        {
+            config_map.insert("outcomeDetail", serde_json::json!(err.to_string()));
+        }
+        _ => {}
+    }
+
+    // Errors before anything was received are shown in place of the messages
+    let messages = match &run.error {
+        Some(err) if run.messages.is_empty() => err.to_string(),
+        _ => run.messages.clone(),
     };
+    let summary = generate_summary_markdown(&messages, exit_code, &config_map);
+
+    // Write collected messages to GitHub step summary if in GitHub Actions environment
+    if std::env::var("GITHUB_ACTIONS").is_ok()
+        && let Err(e) = write_step_summary(&summary)
+    {
+        logging::warn(&format!("⚠️  Warning: Failed to write step summary: {}", e));
+        annotate(
+            AnnotationLevel::Warning,
+            &format!("Failed to write step summary: {}", e),
+            None,
+        );
+    }
+    summary
+}
 
-    tracing_subscriber::fmt().with_max_level(log_level).init();
+/// Runs a single ACP session with `model`: connect, send `prompt` and collect the
+/// transcript. Every way the session ends, including a failed connection, is
+/// reported in the returned run.
+async fn run_session(params: AcpClientParams<'_>, model: &str, prompt: &str) -> SessionRun {
+    let AcpClientParams {
+        timeout,
+        debug,
+        budget,
+        output_schema,
+        schema_retries,
+        watchdog,
+        fail_on_stop_reason,
+        on_message,
+        ..
+    } = params;
 
-    println!("🚀 Starting iFlow WebSocket client...");
+    // A signal may arrive between attempts
+    if let Some(err) = cancel::error() {
+        return SessionRun::failed(err);
+    }
+    let _session = cancel::session();
+
+    let mut recorder = MetricsRecorder::start();
+    let activity = Activity::new();
+    let session_span = tracing::info_span!(parent: &telemetry::root(), "session", model = model);
+
+    logging::info("🚀 Starting iFlow WebSocket client...");
 
     // Use LocalSet for spawn_local compatibility
     let local = tokio::task::LocalSet::new();
-    let summary_holder_clone = summary_holder.clone();
     local
         .run_until(async move {
             // Configure client options with WebSocket configuration and custom timeout
             let custom_timeout_secs = timeout as f64;
             let mut process_config = iflow_cli_sdk_rust::types::ProcessConfig::new()
                 .enable_auto_start()
-                .start_port(8090);
+                .start_port(IFLOW_PORT);
 
             if debug {
                 process_config = process_config.enable_debug();
@@ -70,45 +559,104 @@ pub async fn communicate_with_iflow_cli_via_acp(
             // Create and connect client
             let mut client = IFlowClient::new(Some(options));
 
-            println!("🔗 Connecting to iFlow via WebSocket...");
-            client
-                .connect()
-                .await
-                .map_err(|e| format!("Failed to connect: {}", e))?;
-            println!("✅ Connected to iFlow via WebSocket");
+            logging::info("🔗 Connecting to iFlow via WebSocket...");
+            let connect_span = tracing::info_span!(parent: &session_span, "connect");
+            if let Err(e) = client.connect().instrument(connect_span).await {
+                // Stop an auto-started iFlow process before a retry starts another one
+                let _ = client.disconnect().await;
+                return SessionRun::failed(connect_error(e));
+            }
+            recorder.connected();
+            logging::info("✅ Connected to iFlow via WebSocket");
 
             // Receive and process responses
-            println!("📥 Receiving responses...");
+            logging::info("📥 Receiving responses...");
             let mut message_stream = client.messages();
 
             // Store plan entries to track progress
             let mut plan_entries: Vec<(String, iflow_cli_sdk_rust::types::PlanStatus)> = Vec::new();
 
-            let summary_holder_for_task = summary_holder_clone.clone();
+            let budget_exceeded = Arc::new(tokio::sync::Notify::new());
+            let budget_exceeded_for_task = budget_exceeded.clone();
+            // The task reports the response to each prompt and runs until stopped
+            let (prompt_finished, mut prompt_responses) = tokio::sync::mpsc::unbounded_channel();
+            let stop = Arc::new(tokio::sync::Notify::new());
+            let stop_for_task = stop.clone();
+            let activity_for_task = activity.clone();
+            let on_message = on_message.cloned();
             let message_task = tokio::task::spawn_local(async move {
-                let mut stdout = std::io::stdout();
                 let mut collected_messages = String::new();
+                let mut response = String::new();
+                let mut prompt_response = String::new();
+                let mut outcome = SessionOutcome::Finished;
+                let mut budget_error = None;
+                let mut stop_reason = None;
+                let mut budget_tracker = BudgetTracker::new(budget);
+                let mut spans = SessionSpans::new(session_span);
+                // Turn and tool call whose log group is open
+                let mut groups = LogGroups::new();
+                let mut grouped_turn = 0;
+                let mut grouped_tool_call = None;
 
-                while let Some(message) = message_stream.next().await {
+                loop {
+                    let message = tokio::select! {
+                        message = message_stream.next() => message,
+                        _ = stop_for_task.notified() => break,
+                        _ = cancel::cancelled() => break,
+                    };
+                    let Some(message) = message else {
+                        outcome = SessionOutcome::StreamEnded;
+                        break;
+                    };
+                    activity_for_task.touch();
+                    if let Some(handler) = &on_message {
+                        handler.call(&message);
+                    }
                     match message {
                         Message::Assistant { content } => {
-                            print!("🤖 Assistant: {}", content);
-                            if let Err(err) = stdout.flush() {
-                                eprintln!("❌ Error flushing stdout: {}", err);
+                            recorder.assistant_message();
+                            budget_tracker.assistant_message(&content);
+                            spans.event();
+                            spans.turn(budget_tracker.turns());
+                            if grouped_turn != budget_tracker.turns() {
+                                grouped_turn = budget_tracker.turns();
+                                grouped_tool_call = None;
+                                groups.open(&format!("Turn {}", grouped_turn));
+                            }
+                            if let Err(err) = logging::assistant(&content) {
+                                logging::error(&format!("❌ Error flushing stdout: {}", err));
                                 break;
                             }
 
                             // Collect assistant messages for summary
+                            response.push_str(&content);
+                            prompt_response.push_str(&content);
                             collected_messages.push_str(&format!("\n🤖 Assistant: {}", content));
                         }
                         Message::ToolCall { id, name, status } => {
-                            println!("🔧 Tool call: {} ({}) {:?}", id, name, status);
+                            recorder.tool_call(&id, &name, &status);
+                            budget_tracker.tool_call(&id);
+                            spans.turn(budget_tracker.turns());
+                            spans.tool_call(&id, &name);
+                            if grouped_tool_call.as_ref() != Some(&id) {
+                                groups.open(&format!("Tool call: {} ({})", name, id));
+                                grouped_tool_call = Some(id.clone());
+                                grouped_turn = 0;
+                            }
+                            logging::event(
+                                Level::Info,
+                                "tool_call",
+                                &format!("🔧 Tool call: {} ({}) {:?}", id, name, status),
+                                serde_json::json!({ "id": id, "name": name, "status": status }),
+                            );
 
                             // Collect tool call messages for summary
                             collected_messages
                                 .push_str(&format!("🔧 Tool call: {} ({}) {:?}", id, name, status));
                         }
                         Message::Plan { entries } => {
+                            recorder.event();
+                            spans.event();
                             // Update plan entries
                             plan_entries.clear();
                             for entry in entries {
@@ -117,7 +665,10 @@ pub async fn communicate_with_iflow_cli_via_acp(
 
                             // Display all plan entries with status
                             if !plan_entries.is_empty() {
-                                println!("📋 Plan:");
+                                groups.open("Plan");
+                                grouped_turn = 0;
+                                grouped_tool_call = None;
+                                let mut plan = String::from("📋 Plan:");
                                 collected_messages.push_str("\n📋 Plan:");
                                 for (i, (content, status)) in plan_entries.iter().enumerate() {
                                     let status_icon = match status {
@@ -125,7 +676,12 @@ pub async fn communicate_with_iflow_cli_via_acp(
                                         iflow_cli_sdk_rust::types::PlanStatus::InProgress => "🔄",
                                         iflow_cli_sdk_rust::types::PlanStatus::Completed => "✅",
                                     };
-                                    println!("  {}. {} {}", i + 1, status_icon, content);
+                                    plan.push_str(&format!(
+                                        "\n  {}. {} {}",
+                                        i + 1,
+                                        status_icon,
+                                        content
+                                    ));
                                     collected_messages.push_str(&format!(
                                         "{}. {} {}",
                                         i + 1,
@@ -133,119 +689,314 @@ pub async fn communicate_with_iflow_cli_via_acp(
                                         content
                                     ));
                                 }
+                                let entries: Vec<serde_json::Value> = plan_entries
+                                    .iter()
+                                    .map(|(content, status)| {
+                                        serde_json::json!({
+                                            "content": content,
+                                            "status": format!("{:?}", status),
+                                        })
+                                    })
+                                    .collect();
+                                logging::event(
+                                    Level::Info,
+                                    "plan",
+                                    &plan,
+                                    serde_json::json!({ "entries": entries }),
+                                );
                             }
                         }
-                        Message::TaskFinish { .. } => {
-                            println!("✅ Task completed");
-                            collected_messages.push_str("\n✅ Task completed");
-                            break;
+                        Message::TaskFinish { reason } => {
+                            groups.close();
+                            stop_reason = reason.as_deref().and_then(StopReason::parse);
+                            let finished = match (stop_reason, &reason) {
+                                (Some(StopReason::EndTurn), _) | (None, None) => {
+                                    "✅ Task completed".to_string()
+                                }
+                                (Some(stop_reason), _) => {
+                                    format!("⚠️  Task stopped: {}", stop_reason.as_str())
+                                }
+                                (None, Some(reason)) => format!("⚠️  Task stopped: {}", reason),
+                            };
+                            logging::event(
+                                Level::Info,
+                                "task_finish",
+                                &finished,
+                                serde_json::json!({
+                                    "reason": reason,
+                                    "stopReason": stop_reason.map(|r| r.as_str()),
+                                }),
+                            );
+                            collected_messages.push_str(&format!("\n{}", finished));
+                            if prompt_finished
+                                .send(std::mem::take(&mut prompt_response))
+                                .is_err()
+                            {
+                                break;
+                            }
                         }
                         Message::Error {
                             code,
                             message: msg,
-                            details: _,
+                            details,
                         } => {
-                            eprintln!("❌ Error {}: {}", code, msg);
+                            groups.close();
+                            let location = details.as_ref().and_then(Location::from_details);
+                            annotate(
+                                AnnotationLevel::Error,
+                                &format!("iFlow error {}: {}", code, msg),
+                                location.as_ref(),
+                            );
+                            logging::event(
+                                Level::Error,
+                                "model_error",
+                                &format!("❌ Error {}: {}", code, msg),
+                                serde_json::json!({ "code": code }),
+                            );
                             collected_messages.push_str(&format!("\n❌ Error {}: {}", code, msg));
+                            outcome = SessionOutcome::ModelError {
+                                code,
+                                message: msg,
+                                details: details.map(|details| {
+                                    serde_json::Value::Object(details.into_iter().collect())
+                                }),
+                            };
                             break;
                         }
                         Message::User { content } => {
-                            println!("👤 User message: {}", content);
+                            recorder.event();
+                            spans.event();
+                            groups.close();
+                            grouped_turn = 0;
+                            grouped_tool_call = None;
+                            logging::event(
+                                Level::Info,
+                                "user",
+                                &format!("👤 User message: {}", content),
+                                serde_json::Value::Null,
+                            );
                             collected_messages.push_str(&format!("\n👤 User message: {}", content));
                         }
                     }
+
+                    // Cancel the session once any budget is exceeded
+                    if let Some(exceeded) = budget_tracker.exceeded() {
+                        groups.close();
+                        logging::error(&format!("🛑 Budget exceeded: {}", exceeded));
+                        annotate(
+                            AnnotationLevel::Error,
+                            &format!("Budget exceeded: {}", exceeded),
+                            None,
+                        );
+                        collected_messages.push_str(&format!("\n🛑 Budget exceeded: {}", exceeded));
+                        budget_error = Some(Error::Budget(exceeded));
+                        budget_exceeded_for_task.notify_one();
+                        break;
+                    }
                 }
+                let _ = logging::flush_assistant();
+                let mut metrics = recorder.finish();
+                metrics.output_tokens = Some(budget_tracker.tokens());
 
-                collected_messages
+                CollectedSession {
+                    output: SessionOutput {
+                        summary: None,
+                        metrics,
+                        response,
+                        structured_result: None,
+                        stop_reason,
+                    },
+                    messages: collected_messages,
+                    outcome,
+                    budget_error,
+                }
             });
 
-            // Send a prompt message
-            // println!("📤 User prompt: {}", prompt);
-
-            // Handle the send_message result to catch timeout errors
-            match client.send_message(prompt, None).await {
-                Ok(()) => {
-                    println!("✅ Prompt message sent successfully");
-                }
-                Err(IFlowError::Timeout(msg)) => {
-                    eprintln!("⏰ Timeout error occurred: {}", msg);
-                    eprintln!("This may be due to processing delays.");
-                    eprintln!("Consider increasing the timeout or checking the iFlow process.");
-                }
-                Err(e) => {
-                    eprintln!("❌ Error sending message: {}", e);
-                    return Err(format!("{}", e));
+            // Send the prompt, then a correction prompt while the answer does not
+            // match the output schema
+            let mut next_prompt = prompt.to_string();
+            let mut prompt_chars = 0;
+            let mut corrections = 0;
+            let mut structured_result = None;
+            let mut schema_error = None;
+            let mut stalled_error = None;
+            let mut send_failure = None;
+            // iFlow processes still working on an abandoned prompt
+            let mut abandoned = Vec::new();
+            let prompt_outcome = loop {
+                activity.touch();
+                prompt_chars += next_prompt.chars().count();
+                // Handle the send_message result to catch timeout errors; sending only
+                // completes with the prompt, so an exceeded budget or a signal stops
+                // waiting for it
+                let send_result = tokio::select! {
+                    result = client.send_message(&next_prompt, None) => Some(result),
+                    _ = budget_exceeded.notified() => None,
+                    _ = cancel::cancelled() => None,
+                    idle = watchdog::stalled(&activity, watchdog) => {
+                        let stalled =
+                            format!("no message from iFlow for {}", format_duration(idle));
+                        logging::error(&format!("💤 Session stalled: {}", stalled));
+                        annotate(
+                            AnnotationLevel::Error,
+                            &format!("iFlow stalled: {}", stalled),
+                            None,
+                        );
+                        stalled_error = Some(Error::Stalled(stalled));
+                        None
+                    }
+                };
+                match send_result {
+                    None => {
+                        logging::info("🛑 Cancelling session");
+                        abandoned = cancel::iflow_process_tree();
+                        break None;
+                    }
+                    Some(Ok(())) => {
+                        logging::info("✅ Prompt message sent successfully");
+                    }
+                    Some(Err(IFlowError::Timeout(msg))) => {
+                        logging::warn(&format!("⏰ Timeout error occurred: {}", msg));
+                        logging::warn("This may be due to processing delays.");
+                        logging::warn(
+                            "Consider increasing the timeout or checking the iFlow process.",
+                        );
+                        annotate(
+                            AnnotationLevel::Error,
+                            &format!("iFlow timed out after {}s: {}", timeout, msg),
+                            None,
+                        );
+                        break Some(SessionOutcome::Timeout);
+                    }
+                    Some(Err(e)) => {
+                        logging::error(&format!("❌ Error sending message: {}", e));
+                        match send_error(e) {
+                            Error::Connection(msg) => break Some(SessionOutcome::Disconnected(msg)),
+                            err => {
+                                send_failure = Some(err);
+                                break None;
+                            }
+                        }
+                    }
                 }
-            }
 
-            // Wait for the message handling task to finish with a timeout
-            let message_result = match tokio::time::timeout(
-                std::time::Duration::from_secs_f64(custom_timeout_secs),
-                message_task,
-            )
-            .await
-            {
-                Ok(Ok(collected_messages)) => {
-                    println!("✅ Message handling completed successfully");
-
-                    // Prepare configuration map for summary generation
-                    let mut config_map = std::collections::HashMap::new();
-                    config_map.insert("isTimeout", serde_json::Value::Bool(false));
-                    config_map.insert(
-                        "timeout",
-                        serde_json::Value::Number(serde_json::Number::from(timeout)),
-                    );
-                    config_map.insert("model", serde_json::Value::String(model.to_string()));
-                    config_map.insert("baseURL", serde_json::Value::String(base_url.to_string()));
-                    config_map.insert(
-                        "workingDir",
-                        serde_json::Value::String(working_directory.to_string()),
-                    );
-                    config_map.insert("prompt", serde_json::Value::String(prompt.to_string()));
-
-                    // Generate summary
-                    let summary_content =
-                        generate_summary_markdown(&collected_messages, 0, &config_map);
-
-                    // Write collected messages to GitHub step summary if in GitHub Actions environment
-                    if std::env::var("GITHUB_ACTIONS").is_ok()
-                        && let Err(e) = write_step_summary(&summary_content)
-                    {
-                        eprintln!("⚠️  Warning: Failed to write step summary: {}", e);
+                // Wait for the prompt to finish; the task stops early on errors,
+                // exceeded budgets and signals
+                let prompt_response = match tokio::time::timeout(
+                    std::time::Duration::from_secs_f64(custom_timeout_secs),
+                    prompt_responses.recv(),
+                )
+                .await
+                {
+                    Ok(Some(prompt_response)) => prompt_response,
+                    Ok(None) => break None,
+                    Err(_) => {
+                        logging::warn("⏰ Timeout waiting for message handling to complete");
+                        annotate(
+                            AnnotationLevel::Error,
+                            &format!("iFlow timed out after {}s", timeout),
+                            None,
+                        );
+                        break Some(SessionOutcome::Timeout);
                     }
+                };
 
-                    // Store the generated summary into the shared holder so the outer
-                    // function can access it after the LocalSet completes.
-                    if let Ok(mut guard) = summary_holder_for_task.lock() {
-                        *guard = Some(summary_content.clone());
+                let Some(schema) = output_schema else {
+                    break None;
+                };
+                match schema.validate(&prompt_response) {
+                    Ok(value) => {
+                        logging::info("✅ Structured result matches the output schema");
+                        structured_result = Some(value);
+                        break None;
+                    }
+                    Err(errors) if corrections < schema_retries => {
+                        corrections += 1;
+                        logging::warn(&format!(
+                            "⚠️  Structured result does not match the output schema, asking for a correction ({}/{}): {}",
+                            corrections,
+                            schema_retries,
+                            errors.join("; ")
+                        ));
+                        next_prompt = schema.correction_prompt(&errors);
+                    }
+                    Err(errors) => {
+                        annotate(
+                            AnnotationLevel::Error,
+                            &format!(
+                                "Structured result does not match the output schema: {}",
+                                errors.join("; ")
+                            ),
+                            None,
+                        );
+                        schema_error = Some(Error::Schema(format!(
+                            "structured result does not match output_schema after {} correction(s): {}",
+                            corrections,
+                            errors.join("; ")
+                        )));
+                        break None;
                     }
-
-                    Ok(())
                 }
-                Ok(Err(err)) => {
-                    eprintln!("❌ Error in message handling: {}", err);
-                    Err(format!("Error in message handling: {}", err))
+            };
+
+            // Stop the message handling task and collect its results
+            stop.notify_one();
+            let mut run = match message_task.await {
+                Ok(CollectedSession {
+                    mut output,
+                    messages,
+                    outcome: task_outcome,
+                    budget_error,
+                }) => {
+                    output.metrics.input_tokens = Some(estimate_tokens(prompt_chars));
+                    output.structured_result = structured_result;
+                    let outcome = prompt_outcome.unwrap_or(task_outcome);
+                    let stop_reason_error = output
+                        .stop_reason
+                        .filter(|reason| fail_on_stop_reason.contains(reason))
+                        .map(|reason| Error::StopReason(reason.as_str().to_string()));
+                    let error = cancel::error()
+                        .or(send_failure)
+                        .or(stalled_error)
+                        .or(budget_error)
+                        .or(outcome.error(timeout))
+                        .or(stop_reason_error)
+                        .or(schema_error);
+                    match &error {
+                        None => logging::info("✅ Message handling completed successfully"),
+                        Some(err) => logging::error(&format!("❌ Model {} failed: {}", model, err)),
+                    }
+                    SessionRun {
+                        output,
+                        messages,
+                        outcome: Some(outcome),
+                        error,
+                    }
                 }
-                Err(_) => {
-                    println!("⏰ Timeout waiting for message handling to complete");
-                    Err("Timeout waiting for message handling to complete".to_string())
+                Err(err) => {
+                    logging::error(&format!("❌ Error in message handling: {}", err));
+                    SessionRun::failed(Error::Session(format!(
+                        "Error in message handling: {}",
+                        err
+                    )))
                 }
             };
 
             // Disconnect
-            println!("🔌 Disconnecting...");
-            client
-                .disconnect()
-                .await
-                .map_err(|e| format!("Failed to disconnect: {}", e))?;
-            println!("👋 Disconnected from iFlow");
+            logging::info("🔌 Disconnecting...");
+            match client.disconnect().await {
+                Ok(()) => logging::info("👋 Disconnected from iFlow"),
+                Err(e) => {
+                    run.error
+                        .get_or_insert(Error::Session(format!("Failed to disconnect: {}", e)));
+                }
+            }
+
+            // The SDK cannot stop an iFlow process whose prompt was abandoned; its
+            // tree is taken before disconnecting, which may orphan its children
+            cancel::terminate(&abandoned);
 
-            message_result.map_err(|e| format!("WebSocket client error: {}", e))
+            run
         })
         .await
-        .map_err(|e| format!("WebSocket client error: {}", e))?;
-
-    // Extract the summary from the holder and return it
-    let summary = summary_holder.lock().map(|g| g.clone()).unwrap_or(None);
-    Ok(summary)
 }
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+/// Approximate number of characters per token used to estimate tokens
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimated number of tokens in `chars` characters of text
+pub fn estimate_tokens(chars: usize) -> u64 {
+    chars.div_ceil(CHARS_PER_TOKEN) as u64
+}
+
+/// Limits on a session beyond the wall-clock timeout; `None` disables a limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionBudget {
+    pub max_turns: Option<u32>,
+    pub max_tool_calls: Option<u32>,
+    pub max_tokens: Option<u64>,
+}
+
+/// Tracks session usage against a [`SessionBudget`].
+///
+/// A turn starts with the first agent event and with every assistant message
+/// that follows a tool call. iFlow does not report token usage over ACP, so
+/// tokens are estimated from the assistant output.
+#[derive(Debug, Default)]
+pub struct BudgetTracker {
+    budget: SessionBudget,
+    turns: u32,
+    tool_calls: HashSet<String>,
+    output_chars: usize,
+    after_tool_call: bool,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: SessionBudget) -> Self {
+        BudgetTracker {
+            budget,
+            ..Default::default()
+        }
+    }
+
+    /// Records an assistant message
+    pub fn assistant_message(&mut self, content: &str) {
+        if self.turns == 0 || self.after_tool_call {
+            self.turns += 1;
+            self.after_tool_call = false;
+        }
+        self.output_chars += content.chars().count();
+    }
+
+    /// Records a tool call; repeated events for the same call count once
+    pub fn tool_call(&mut self, id: &str) {
+        if self.turns == 0 {
+            self.turns = 1;
+        }
+        self.after_tool_call = true;
+        self.tool_calls.insert(id.to_string());
+    }
+
+    /// Number of turns so far
+    pub fn turns(&self) -> u32 {
+        self.turns
+    }
+
+    /// Estimated output tokens so far
+    pub fn tokens(&self) -> u64 {
+        estimate_tokens(self.output_chars)
+    }
+
+    /// Describes the first exceeded limit, if any
+    pub fn exceeded(&self) -> Option<String> {
+        let over = |limit: Option<u64>, used: u64| limit.filter(|limit| used > *limit);
+
+        if let Some(limit) = over(self.budget.max_turns.map(u64::from), self.turns.into()) {
+            return Some(format!("max_turns of {} exceeded", limit));
+        }
+        if let Some(limit) = over(
+            self.budget.max_tool_calls.map(u64::from),
+            self.tool_calls.len() as u64,
+        ) {
+            return Some(format!("max_tool_calls of {} exceeded", limit));
+        }
+        if let Some(limit) = over(self.budget.max_tokens, self.tokens()) {
+            return Some(format!(
+                "max_tokens of {} exceeded (about {} tokens generated)",
+                limit,
+                self.tokens()
+            ));
+        }
+        None
+    }
+}
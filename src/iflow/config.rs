@@ -1,6 +1,26 @@
+use crate::error::{Error, Result};
 use serde_json;
 use std::fs;
 
+/// Authentication types supported by iFlow CLI settings (`selectedAuthType`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AuthType {
+    /// iFlow platform authentication with an iFlow API key
+    Iflow,
+    /// Any model provider exposing an OpenAI-compatible API
+    OpenaiCompatible,
+}
+
+impl AuthType {
+    /// Returns the value used for `selectedAuthType` in settings.json
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthType::Iflow => "iflow",
+            AuthType::OpenaiCompatible => "openai-compatible",
+        }
+    }
+}
+
 /// IFlow configuration
 #[derive(Debug)]
 pub struct IFlowConfig {
@@ -9,56 +29,76 @@ pub struct IFlowConfig {
 
     /// Model name to use
     pub model: String,
+
+    /// Authentication type written to `selectedAuthType`
+    pub auth_type: AuthType,
+
+    /// API key for the web search service, separate from the model API key
+    pub search_api_key: Option<String>,
+
+    /// Visual theme of iFlow CLI
+    pub theme: String,
+
+    /// Maximum number of turns iFlow CLI runs per session (`maxSessionTurns`)
+    pub max_session_turns: Option<u32>,
+
+    /// OTLP endpoint that iFlow CLI also sends its telemetry to (`telemetry`)
+    pub otlp_endpoint: Option<String>,
 }
 
 impl IFlowConfig {
     /// Creates settings JSON from individual parameters
-    pub fn create_settings_from_params(&self, api_key: &str) -> Result<String, String> {
-        let settings = serde_json::json!({
-            "theme": "Default",
-            "selectedAuthType": "iflow",
+    pub fn create_settings_from_params(&self, api_key: &str) -> Result<String> {
+        let mut settings = serde_json::json!({
+            "theme": self.theme,
+            "selectedAuthType": self.auth_type.as_str(),
             "apiKey": api_key,
             "baseUrl": self.base_url,
             "modelName": self.model,
-            "searchApiKey": api_key
         });
 
-        serde_json::to_string_pretty(&settings)
-            .map_err(|e| format!("failed to marshal settings: {}", e))
-    }
+        // iFlow API keys also work for search; other providers need a dedicated key
+        let search_api_key = match (&self.search_api_key, self.auth_type) {
+            (Some(key), _) if !key.is_empty() => Some(key.as_str()),
+            (_, AuthType::Iflow) => Some(api_key),
+            _ => None,
+        };
+        if let Some(search_api_key) = search_api_key {
+            settings["searchApiKey"] = serde_json::Value::String(search_api_key.to_string());
+        }
 
-    /// Configures iFlow settings
-    pub fn configure(
-        &self,
-        settings_json: Option<&String>,
-        api_key: &str,
-        settings_file_path: Option<&String>,
-    ) -> Result<(), String> {
-        // Determine the settings file path
-        let settings_file_path = if let Some(path) = settings_file_path {
-            path.clone()
-        } else {
-            // Get home directory
-            let home_dir = dirs::home_dir().ok_or("failed to get home directory")?;
+        if let Some(max_session_turns) = self.max_session_turns {
+            settings["maxSessionTurns"] = serde_json::json!(max_session_turns);
+        }
 
-            // Create .iflow directory
-            let iflow_dir = home_dir.join(".iflow");
-            fs::create_dir_all(&iflow_dir)
-                .map_err(|e| format!("failed to create .iflow directory: {}", e))?;
+        // Prompts may contain secrets, so iFlow CLI never logs them
+        if let Some(otlp_endpoint) = self.otlp_endpoint.as_deref().filter(|e| !e.is_empty()) {
+            settings["telemetry"] = serde_json::json!({
+                "enabled": true,
+                "target": "local",
+                "otlpEndpoint": otlp_endpoint,
+                "logPrompts": false,
+            });
+        }
 
-            // Path to settings.json file
-            let settings_file = iflow_dir.join("settings.json");
-            settings_file.to_string_lossy().to_string()
-        };
+        serde_json::to_string_pretty(&settings)
+            .map_err(|e| Error::Configuration(format!("failed to marshal settings: {}", e)))
+    }
 
+    /// Returns the settings.json content: `settings_json` when provided,
+    /// otherwise settings created from the individual parameters
+    pub fn settings(&self, settings_json: Option<&String>, api_key: &str) -> Result<String> {
         let settings_data = if let Some(settings_json) = settings_json {
             if !settings_json.is_empty() {
                 // Use provided settings JSON directly
                 // Pretty format the JSON
-                let parsed: serde_json::Value = serde_json::from_str(settings_json)
-                    .map_err(|e| format!("invalid settings_json provided: {}", e))?;
-                serde_json::to_string_pretty(&parsed)
-                    .map_err(|e| format!("failed to format settings JSON: {}", e))?
+                let parsed: serde_json::Value =
+                    serde_json::from_str(settings_json).map_err(|e| {
+                        Error::Configuration(format!("invalid settings_json provided: {}", e))
+                    })?;
+                serde_json::to_string_pretty(&parsed).map_err(|e| {
+                    Error::Configuration(format!("failed to format settings JSON: {}", e))
+                })?
             } else {
                 // Create settings from individual parameters
                 self.create_settings_from_params(api_key)?
@@ -67,17 +107,111 @@ impl IFlowConfig {
             // Create settings from individual parameters
             self.create_settings_from_params(api_key)?
         };
+        Ok(settings_data)
+    }
+
+    /// Configures iFlow settings
+    pub fn configure(
+        &self,
+        settings_json: Option<&String>,
+        api_key: &str,
+        settings_file_path: Option<&String>,
+    ) -> Result<()> {
+        // Determine the settings file path
+        let settings_file_path = Self::resolve_settings_file_path(settings_file_path)?;
+        let settings_data = self.settings(settings_json, api_key)?;
 
         // Write settings to file
         // Ensure the parent directory exists
         if let Some(parent) = std::path::Path::new(&settings_file_path).parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("failed to create parent directory: {}", e))?;
+            fs::create_dir_all(parent).map_err(|e| {
+                Error::Configuration(format!("failed to create parent directory: {}", e))
+            })?;
         }
 
         fs::write(&settings_file_path, settings_data)
-            .map_err(|e| format!("failed to write settings file: {}", e))?;
+            .map_err(|e| Error::Configuration(format!("failed to write settings file: {}", e)))?;
 
         Ok(())
     }
+
+    /// Switches to another model by rewriting `modelName` in the configured settings file,
+    /// preserving every other setting (including a user-provided settings_json)
+    pub fn set_model(&mut self, model: &str, settings_file_path: Option<&String>) -> Result<()> {
+        let settings_file_path = Self::resolve_settings_file_path(settings_file_path)?;
+
+        let content = fs::read_to_string(&settings_file_path)
+            .map_err(|e| Error::Configuration(format!("failed to read settings file: {}", e)))?;
+        let mut settings: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| Error::Configuration(format!("invalid settings file: {}", e)))?;
+        settings["modelName"] = serde_json::Value::String(model.to_string());
+
+        let settings_data = serde_json::to_string_pretty(&settings)
+            .map_err(|e| Error::Configuration(format!("failed to format settings JSON: {}", e)))?;
+        fs::write(&settings_file_path, settings_data)
+            .map_err(|e| Error::Configuration(format!("failed to write settings file: {}", e)))?;
+
+        self.model = model.to_string();
+        Ok(())
+    }
+
+    /// Saves the current settings file, if any, before `configure` overwrites it
+    pub fn backup_settings(settings_file_path: Option<&String>) -> Result<SettingsBackup> {
+        let path = Self::resolve_settings_file_path(settings_file_path)?;
+        let original = match fs::read(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(Error::Configuration(format!(
+                    "failed to read settings file: {}",
+                    e
+                )));
+            }
+        };
+        Ok(SettingsBackup { path, original })
+    }
+
+    /// Returns the settings file path, defaulting to `~/.iflow/settings.json`
+    fn resolve_settings_file_path(settings_file_path: Option<&String>) -> Result<String> {
+        if let Some(path) = settings_file_path {
+            return Ok(path.clone());
+        }
+
+        // Get home directory
+        let home_dir = dirs::home_dir()
+            .ok_or_else(|| Error::Configuration("failed to get home directory".to_string()))?;
+
+        // Create .iflow directory
+        let iflow_dir = home_dir.join(".iflow");
+        fs::create_dir_all(&iflow_dir).map_err(|e| {
+            Error::Configuration(format!("failed to create .iflow directory: {}", e))
+        })?;
+
+        // Path to settings.json file
+        let settings_file = iflow_dir.join("settings.json");
+        Ok(settings_file.to_string_lossy().to_string())
+    }
+}
+
+/// Settings file as it was before the action configured iFlow
+#[derive(Debug, Clone)]
+pub struct SettingsBackup {
+    path: String,
+    original: Option<Vec<u8>>,
+}
+
+impl SettingsBackup {
+    /// Puts the original settings file back, or removes the file the action
+    /// created so the API key is not left behind
+    pub fn restore(&self) -> Result<()> {
+        let restored = match &self.original {
+            Some(content) => fs::write(&self.path, content),
+            None => match fs::remove_file(&self.path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+        };
+        restored
+            .map_err(|e| Error::Configuration(format!("failed to restore settings file: {}", e)))
+    }
 }
@@ -0,0 +1,155 @@
+use crate::error::{Error, Result};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// Parses the `model_prices` input, e.g.
+/// `{"qwen3-coder-plus": {"input": 0.5, "output": 2.0}}`
+pub fn parse_model_prices(json: &str) -> Result<HashMap<String, ModelPrice>> {
+    let invalid = |msg: String| Error::Validation(format!("invalid model_prices: {}", msg));
+    let value: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+    let models = value
+        .as_object()
+        .ok_or_else(|| invalid("expected an object keyed by model".to_string()))?;
+
+    models
+        .iter()
+        .map(|(model, price)| {
+            let field = |name: &str| {
+                price[name].as_f64().filter(|p| *p >= 0.0).ok_or_else(|| {
+                    invalid(format!("'{}' needs a non-negative '{}' price", model, name))
+                })
+            };
+            Ok((
+                model.clone(),
+                ModelPrice {
+                    input: field("input")?,
+                    output: field("output")?,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Duration of a single tool call
+#[derive(Debug, Clone)]
+pub struct ToolCallTiming {
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// Timing and usage metrics of an ACP session
+#[derive(Debug, Clone, Default)]
+pub struct SessionMetrics {
+    /// Time to connect to iFlow, including starting the process
+    pub connect: Duration,
+    /// Time from the start of the session to the first assistant message
+    pub first_token: Option<Duration>,
+    /// Total session duration
+    pub duration: Duration,
+    pub tool_calls: Vec<ToolCallTiming>,
+    /// Estimated tokens of the prompts sent; iFlow does not report usage over
+    /// ACP, and the context it adds itself (tools, files) is not included
+    pub input_tokens: Option<u64>,
+    /// Estimated tokens of the assistant output
+    pub output_tokens: Option<u64>,
+}
+
+impl SessionMetrics {
+    /// Estimated cost in USD from the estimated token usage
+    pub fn estimate_cost(&self, price: &ModelPrice) -> Option<f64> {
+        let input = self.input_tokens? as f64;
+        let output = self.output_tokens? as f64;
+        Some((input * price.input + output * price.output) / 1_000_000.0)
+    }
+
+    /// JSON form used by the summary
+    pub fn to_json(&self, price: Option<&ModelPrice>) -> Value {
+        json!({
+            "connectMs": self.connect.as_millis() as u64,
+            "firstTokenMs": self.first_token.map(|d| d.as_millis() as u64),
+            "durationMs": self.duration.as_millis() as u64,
+            "toolCalls": self.tool_calls.iter().map(|t| json!({
+                "name": t.name,
+                "durationMs": t.duration.as_millis() as u64,
+            })).collect::<Vec<_>>(),
+            "inputTokens": self.input_tokens,
+            "outputTokens": self.output_tokens,
+            "estimatedCostUsd": price.and_then(|p| self.estimate_cost(p)),
+        })
+    }
+}
+
+/// Records metrics while a session runs.
+///
+/// A tool call runs until iFlow reports it completed or failed; the SDK does
+/// not forward every status update, so the next session event also ends it.
+#[derive(Debug)]
+pub struct MetricsRecorder {
+    started: Instant,
+    metrics: SessionMetrics,
+    running_tool_call: Option<(String, String, Instant)>,
+}
+
+impl MetricsRecorder {
+    pub fn start() -> Self {
+        MetricsRecorder {
+            started: Instant::now(),
+            metrics: SessionMetrics::default(),
+            running_tool_call: None,
+        }
+    }
+
+    /// Marks the connection as established
+    pub fn connected(&mut self) {
+        self.metrics.connect = self.started.elapsed();
+    }
+
+    /// Records a session event, ending a running tool call
+    pub fn event(&mut self) {
+        if let Some((_, name, started)) = self.running_tool_call.take() {
+            self.metrics.tool_calls.push(ToolCallTiming {
+                name,
+                duration: started.elapsed(),
+            });
+        }
+    }
+
+    /// Records an assistant message
+    pub fn assistant_message(&mut self) {
+        self.event();
+        self.metrics
+            .first_token
+            .get_or_insert_with(|| self.started.elapsed());
+    }
+
+    /// Records a tool call event; repeated events for the same call keep it
+    /// running until its `status` is completed or failed
+    pub fn tool_call(&mut self, id: &str, name: &str, status: &str) {
+        let running = self
+            .running_tool_call
+            .as_ref()
+            .is_some_and(|(running, _, _)| running == id);
+        if !running {
+            self.event();
+            self.running_tool_call = Some((id.to_string(), name.to_string(), Instant::now()));
+        }
+        if status.eq_ignore_ascii_case("completed") || status.eq_ignore_ascii_case("failed") {
+            self.event();
+        }
+    }
+
+    /// Stops recording and returns the metrics
+    pub fn finish(mut self) -> SessionMetrics {
+        self.event();
+        self.metrics.duration = self.started.elapsed();
+        self.metrics
+    }
+}
@@ -3,6 +3,18 @@
 //! This module contains all iFlow-specific functionality including:
 //! - Configuration management
 //! - ACP client implementation for communicating with iFlow CLI
+//! - Retry policy for transient failures
+//! - Timing, usage and cost metrics of a session
+//! - Turn, tool call and token budgets of a session
+//! - Structured results validated against a JSON Schema
+//! - Inactivity watchdog and progress heartbeats of a session
+//! - Stop reasons reported when the agent finishes a prompt
 
 pub mod acp_client;
+pub mod budget;
 pub mod config;
+pub mod metrics;
+pub mod retry;
+pub mod stop_reason;
+pub mod structured;
+pub mod watchdog;
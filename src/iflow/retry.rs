@@ -0,0 +1,75 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Classes of transient failures that may be retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorClass {
+    /// Connecting to iFlow or the transport to it failed
+    Connection,
+    /// The API rejected the request because of rate limiting (HTTP 429)
+    #[value(alias = "rate_limit")]
+    RateLimit,
+    /// The API reported a server-side failure (HTTP 5xx)
+    #[value(alias = "server_error")]
+    ServerError,
+    /// The session did not finish within the configured timeout
+    Timeout,
+}
+
+impl ErrorClass {
+    /// Classifies a model/API error code; codes such as 401 or 403 are permanent
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            429 => Some(ErrorClass::RateLimit),
+            500..=599 => Some(ErrorClass::ServerError),
+            _ => None,
+        }
+    }
+}
+
+/// Retry policy applied to connecting and running an ACP session
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for every further retry
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between retries
+    pub max_delay: Duration,
+    /// Randomize delays between 50% and 100% to avoid synchronized retries
+    pub jitter: bool,
+    /// Error classes that are retried
+    pub retry_on: Vec<ErrorClass>,
+}
+
+impl RetryPolicy {
+    /// Whether a failure of the given class may be retried
+    pub fn is_retryable(&self, class: Option<ErrorClass>) -> bool {
+        class.is_some_and(|c| self.retry_on.contains(&c))
+    }
+
+    /// Delay before retry number `retry` (starting at 1)
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2f64.powi(retry.saturating_sub(1).min(30) as i32);
+        let delay = self
+            .initial_delay
+            .mul_f64(factor)
+            .min(self.max_delay.max(self.initial_delay));
+
+        if self.jitter {
+            delay.mul_f64(0.5 + random_fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Returns a pseudo-random value in [0, 1) derived from the system clock
+fn random_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    // Scramble the low bits so consecutive calls do not yield similar values
+    let mixed = nanos.wrapping_mul(2_654_435_761) % 1_000_000;
+    mixed as f64 / 1_000_000.0
+}
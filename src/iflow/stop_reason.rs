@@ -0,0 +1,49 @@
+/// Why the agent stopped working on a prompt, from `TaskFinish`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum StopReason {
+    /// The agent finished its turn
+    #[value(alias = "end_turn")]
+    EndTurn,
+    /// The model reached its output token limit
+    #[value(alias = "max_tokens")]
+    MaxTokens,
+    /// The agent reached the maximum number of requests in a turn
+    #[value(alias = "max_turn_requests")]
+    MaxTurnRequests,
+    /// The model refused to continue
+    Refusal,
+    /// The prompt was cancelled or interrupted
+    Cancelled,
+}
+
+impl StopReason {
+    /// Parses the reason reported with `TaskFinish`. The WebSocket transport
+    /// only reports `completed`, which is read as the end of the turn; the
+    /// stdio transport reports the ACP stop reason, e.g. `MaxTokens`.
+    pub fn parse(reason: &str) -> Option<StopReason> {
+        let normalized: String = reason
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.as_str() {
+            "endturn" | "completed" => Some(StopReason::EndTurn),
+            "maxtokens" => Some(StopReason::MaxTokens),
+            "maxturnrequests" => Some(StopReason::MaxTurnRequests),
+            "refusal" => Some(StopReason::Refusal),
+            "cancelled" | "canceled" | "interrupted" => Some(StopReason::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// Value of the `stop_reason` output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::EndTurn => "end_turn",
+            StopReason::MaxTokens => "max_tokens",
+            StopReason::MaxTurnRequests => "max_turn_requests",
+            StopReason::Refusal => "refusal",
+            StopReason::Cancelled => "cancelled",
+        }
+    }
+}
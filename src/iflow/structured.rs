@@ -0,0 +1,107 @@
+use crate::error::{Error, Result};
+use serde_json::Value;
+
+/// JSON values found in the fenced code blocks of `text`, in order
+pub fn fenced_json_blocks(text: &str) -> impl Iterator<Item = Value> + '_ {
+    text.split("```").skip(1).step_by(2).filter_map(|block| {
+        let block = block.strip_prefix("json").unwrap_or(block);
+        serde_json::from_str(block.trim()).ok()
+    })
+}
+
+/// Extracts the JSON answer from assistant text: the last fenced JSON block,
+/// or else the whole text, or else the outermost object or array in it
+pub fn extract_json(text: &str) -> Option<Value> {
+    if let Some(value) = fenced_json_blocks(text).last() {
+        return Some(value);
+    }
+    if let Ok(value) = serde_json::from_str(text.trim()) {
+        return Some(value);
+    }
+
+    ['{', '[']
+        .iter()
+        .zip(['}', ']'])
+        .filter_map(|(open, close)| {
+            let start = text.find(*open)?;
+            let end = text.rfind(close)?;
+            (start < end).then(|| &text[start..=end])
+        })
+        .find_map(|candidate| serde_json::from_str(candidate).ok())
+}
+
+/// JSON Schema that the agent's final answer must match
+#[derive(Debug, Clone)]
+pub struct OutputSchema {
+    schema: Value,
+    validator: jsonschema::Validator,
+}
+
+impl OutputSchema {
+    /// Loads the `output_schema` input: an inline JSON schema or the path to one
+    pub fn load(input: &str) -> Result<Self> {
+        let invalid = |msg: String| Error::Validation(format!("invalid output_schema: {}", msg));
+        let input = input.trim();
+        let source = if input.starts_with('{') {
+            input.to_string()
+        } else {
+            std::fs::read_to_string(input)
+                .map_err(|e| invalid(format!("failed to read '{}': {}", input, e)))?
+        };
+
+        let schema: Value = serde_json::from_str(&source).map_err(|e| invalid(e.to_string()))?;
+        OutputSchema::from_value(schema).map_err(invalid)
+    }
+
+    /// Compiles a schema value
+    pub fn from_value(schema: Value) -> std::result::Result<Self, String> {
+        let validator = jsonschema::validator_for(&schema).map_err(|e| e.to_string())?;
+        Ok(OutputSchema { schema, validator })
+    }
+
+    /// Instructions appended to the prompt
+    pub fn instructions(&self) -> String {
+        format!(
+            "\n\nEnd your final message with your answer as a single fenced ```json block that validates against this JSON Schema:\n\n```json\n{}\n```",
+            serde_json::to_string_pretty(&self.schema).unwrap_or_default()
+        )
+    }
+
+    /// Extracts the answer from assistant text and validates it, returning
+    /// the validation errors when it is missing or does not match
+    pub fn validate(&self, text: &str) -> std::result::Result<Value, Vec<String>> {
+        let Some(value) = extract_json(text) else {
+            return Err(vec!["no JSON value found in the response".to_string()]);
+        };
+
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .map(|e| {
+                let path = e.instance_path().to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{}: {}", path, e)
+                }
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Follow-up prompt asking the agent to correct an invalid answer
+    pub fn correction_prompt(&self, errors: &[String]) -> String {
+        format!(
+            "Your answer did not validate against the JSON Schema:\n{}\n\nReply with only the corrected answer as a single fenced ```json block.",
+            errors
+                .iter()
+                .map(|e| format!("- {}", e))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+}
@@ -0,0 +1,114 @@
+use crate::logging::{self, Level};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often the watchdog checks for inactivity
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Inactivity limits of a session, separate from the total timeout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WatchdogSettings<'a> {
+    /// Time without any message from iFlow after which the session is stalled
+    pub idle_timeout: Option<Duration>,
+    /// Follow-up prompt of the retry started when a session stalls
+    pub retry_prompt: Option<&'a str>,
+    /// Interval of the progress heartbeats printed while waiting
+    pub heartbeat_interval: Option<Duration>,
+}
+
+impl WatchdogSettings<'_> {
+    /// Prompt of the retry started when a session stalls: the original prompt
+    /// followed by the retry prompt, since the new session has no memory of
+    /// the stalled one. The stalled session itself cannot be followed up, as
+    /// the SDK has no way to cancel the prompt still running there and
+    /// abandoning that prompt leaves its client without a connection.
+    pub fn stalled_retry_prompt(&self, prompt: &str) -> Option<String> {
+        self.retry_prompt
+            .map(|retry_prompt| format!("{}\n\n{}", prompt, retry_prompt))
+    }
+}
+
+/// Time of the last message from iFlow, shared with the message task
+#[derive(Debug, Clone)]
+pub struct Activity {
+    started: Instant,
+    /// Milliseconds between `started` and the last message
+    last: Arc<AtomicU64>,
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        Activity {
+            started: Instant::now(),
+            last: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Records a message
+    pub fn touch(&self) {
+        let now = self.started.elapsed().as_millis() as u64;
+        self.last.store(now, Ordering::Relaxed);
+    }
+
+    /// Time since the session started
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Time since the last message
+    pub fn idle(&self) -> Duration {
+        let last = Duration::from_millis(self.last.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last)
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats a duration as e.g. `1h 2m 3s`, `2m 3s` or `3s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, s) => format!("{}h {}m {}s", h, m, s),
+    }
+}
+
+/// Waits until no message arrived for `idle_timeout` and returns the idle
+/// time, printing a heartbeat every `heartbeat_interval` meanwhile. Never
+/// completes when `idle_timeout` is not set.
+pub async fn stalled(activity: &Activity, settings: WatchdogSettings<'_>) -> Duration {
+    let mut last_heartbeat = Instant::now();
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let idle = activity.idle();
+        if settings.idle_timeout.is_some_and(|limit| idle >= limit) {
+            return idle;
+        }
+
+        if let Some(interval) = settings.heartbeat_interval
+            && last_heartbeat.elapsed() >= interval
+        {
+            last_heartbeat = Instant::now();
+            logging::event(
+                Level::Info,
+                "heartbeat",
+                &format!(
+                    "💓 Still working: {} elapsed, last message {} ago",
+                    format_duration(activity.elapsed()),
+                    format_duration(idle)
+                ),
+                serde_json::json!({
+                    "elapsedSecs": activity.elapsed().as_secs(),
+                    "idleSecs": idle.as_secs(),
+                }),
+            );
+        }
+    }
+}
@@ -1,3 +1,15 @@
-// export functions for testing
+//! iFlow CLI Action as a library: [`Runner`] runs the whole action and the
+//! binary is a thin wrapper around it
+pub mod cancel;
+pub mod cli;
+pub mod error;
+pub mod executor;
 pub mod github;
+pub mod iflow;
+pub mod logging;
+pub mod runner;
+pub mod secrets;
+pub mod telemetry;
+pub mod version_mgr;
 pub use github::summary::{contains_code, generate_summary_markdown};
+pub use runner::{RunReport, Runner};
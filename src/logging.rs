@@ -0,0 +1,344 @@
+use crate::secrets::{StreamRedactor, redact};
+use serde_json::{Map, Value, json};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+
+/// Console output format selected by the `log_format` input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable output with emoji
+    #[default]
+    Pretty,
+    /// Human-readable output without emoji or colors
+    Plain,
+    /// One JSON object per line, for log aggregation
+    Json,
+}
+
+static FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+/// Whether streamed assistant output left the current stdout line unfinished
+static LINE_OPEN: AtomicBool = AtomicBool::new(false);
+
+/// Streamed assistant output held back because it may end in part of a secret
+static ASSISTANT_STREAM: Mutex<StreamRedactor> = Mutex::new(StreamRedactor::new());
+
+/// Sets the output format; only the first call takes effect
+pub fn set_format(format: LogFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// Returns the output format, `pretty` until one is set
+pub fn format() -> LogFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Severity of a console message; warnings and errors go to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Removes emoji (and the spacing after a leading one) from every line
+pub fn strip_emoji(text: &str) -> String {
+    let is_emoji = |c: char| {
+        matches!(c as u32,
+            0x1F000..=0x1FAFF | 0x2300..=0x23FF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D)
+    };
+    text.split('\n')
+        .map(|line| {
+            let stripped: String = line.chars().filter(|c| !is_emoji(*c)).collect();
+            if stripped.len() != line.len() && line.starts_with(|c: char| is_emoji(c)) {
+                stripped.trim_start().to_string()
+            } else {
+                stripped
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats the current time as an RFC 3339 UTC timestamp
+pub fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Writes a JSON log line
+fn write_json(level: &str, event: Option<&str>, message: &str, fields: Map<String, Value>) {
+    let mut line = Map::new();
+    line.insert("timestamp".to_string(), json!(timestamp()));
+    line.insert("level".to_string(), json!(level));
+    if let Some(event) = event {
+        line.insert("event".to_string(), json!(event));
+    }
+    line.insert("message".to_string(), json!(redact(message)));
+    for (key, value) in fields {
+        line.insert(key, value);
+    }
+    println!("{}", redact(&Value::Object(line).to_string()));
+}
+
+/// Prints a message, or a JSON line of kind `event` with extra `fields`
+pub fn event(level: Level, event: &str, message: &str, fields: Value) {
+    let _ = flush_assistant();
+    let fields = match fields {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    };
+    let text = match format() {
+        LogFormat::Json => {
+            return write_json(level.as_str(), Some(event), &strip_emoji(message), fields);
+        }
+        LogFormat::Pretty => redact(message),
+        LogFormat::Plain => strip_emoji(&redact(message)),
+    };
+    match level {
+        Level::Info => println!("{}", text),
+        Level::Warn | Level::Error => eprintln!("{}", text),
+    }
+}
+
+/// Prints an informational message to stdout
+pub fn info(message: &str) {
+    event(Level::Info, "log", message, Value::Null);
+}
+
+/// Prints a warning to stderr
+pub fn warn(message: &str) {
+    event(Level::Warn, "log", message, Value::Null);
+}
+
+/// Prints an error to stderr
+pub fn error(message: &str) {
+    event(Level::Error, "log", message, Value::Null);
+}
+
+/// Streams a chunk of assistant output; pretty and plain output continue on the same line.
+/// A secret split across chunks is still redacted, see [`StreamRedactor`].
+pub fn assistant(content: &str) -> std::io::Result<()> {
+    let text = match ASSISTANT_STREAM.lock() {
+        Ok(mut stream) => stream.push(content),
+        Err(_) => redact(content),
+    };
+    write_assistant(&text)
+}
+
+/// Prints the assistant output held back by [`assistant`]; other output does
+/// this first, and so does the end of a session
+pub fn flush_assistant() -> std::io::Result<()> {
+    let text = match ASSISTANT_STREAM.lock() {
+        Ok(mut stream) => stream.finish(),
+        Err(_) => return Ok(()),
+    };
+    write_assistant(&text)
+}
+
+fn write_assistant(text: &str) -> std::io::Result<()> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    match format() {
+        LogFormat::Json => {
+            write_json("info", Some("assistant"), text, Map::new());
+            Ok(())
+        }
+        LogFormat::Pretty => {
+            LINE_OPEN.store(!text.ends_with('\n'), Ordering::Relaxed);
+            print!("🤖 Assistant: {}", text);
+            std::io::stdout().flush()
+        }
+        LogFormat::Plain => {
+            LINE_OPEN.store(!text.ends_with('\n'), Ordering::Relaxed);
+            print!("Assistant: {}", text);
+            std::io::stdout().flush()
+        }
+    }
+}
+
+/// Prints a Markdown summary; pretty output on a terminal shows headings and
+/// bold text in bold
+pub fn summary(markdown: &str) {
+    let _ = flush_assistant();
+    if LINE_OPEN.swap(false, Ordering::Relaxed) {
+        println!();
+    }
+    match format() {
+        LogFormat::Json => write_json("info", Some("summary"), markdown, Map::new()),
+        LogFormat::Pretty if std::io::stdout().is_terminal() => {
+            println!("{}", render_markdown(&redact(markdown)))
+        }
+        LogFormat::Pretty => println!("{}", redact(markdown)),
+        LogFormat::Plain => println!("{}", strip_emoji(&redact(markdown))),
+    }
+}
+
+/// Renders Markdown for a terminal: headings and `**bold**` text become ANSI
+/// bold, code blocks are left as they are
+pub fn render_markdown(markdown: &str) -> String {
+    const BOLD: &str = "\x1b[1m";
+    const RESET: &str = "\x1b[0m";
+
+    let mut in_code = false;
+    markdown
+        .split('\n')
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                return line.to_string();
+            }
+            if in_code {
+                return line.to_string();
+            }
+            if let Some(heading) = line.strip_prefix('#') {
+                return format!(
+                    "{}{}{}",
+                    BOLD,
+                    heading.trim_start_matches('#').trim(),
+                    RESET
+                );
+            }
+
+            // Only balanced markers are rendered
+            let parts: Vec<&str> = line.split("**").collect();
+            if parts.len().is_multiple_of(2) {
+                return line.to_string();
+            }
+            parts
+                .iter()
+                .enumerate()
+                .map(|(i, part)| {
+                    if i % 2 == 1 {
+                        format!("{}{}{}", BOLD, part, RESET)
+                    } else {
+                        part.to_string()
+                    }
+                })
+                .collect()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes a workflow command such as `::group::` on a line of its own, in every
+/// format. In `json` format it goes to stderr, which the runner also reads
+/// workflow commands from, so that every stdout line stays a JSON object.
+pub fn workflow_command(command: &str) {
+    let _ = flush_assistant();
+    write_workflow_command(&redact(command));
+}
+
+/// Asks the runner to mask `value` in the job log. Called while registering
+/// the secret, so the value is neither redacted nor held up by the assistant
+/// stream.
+pub fn add_mask(value: &str) {
+    write_workflow_command(&format!("::add-mask::{}", value));
+}
+
+fn write_workflow_command(command: &str) {
+    if LINE_OPEN.swap(false, Ordering::Relaxed) {
+        println!();
+    }
+    match format() {
+        LogFormat::Json => eprintln!("{}", command),
+        LogFormat::Pretty | LogFormat::Plain => println!("{}", command),
+    }
+}
+
+/// Collects the fields of a tracing event
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+}
+
+/// Writes tracing events, such as the SDK's logs, as JSON lines
+pub struct JsonLayer;
+
+impl<S: Subscriber> tracing_subscriber::Layer<S> for JsonLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        let meta = event.metadata();
+        let message = match visitor.0.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+
+        let mut fields = visitor.0;
+        fields.insert("target".to_string(), json!(meta.target()));
+        write_json(
+            &meta.level().as_str().to_lowercase(),
+            None,
+            &message,
+            fields,
+        );
+    }
+}
@@ -1,130 +1,233 @@
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use iflow_cli_action::cancel;
+use iflow_cli_action::cli::args::{CliArgs, Command};
+use iflow_cli_action::cli::commands;
+use iflow_cli_action::cli::config_file::{ConfigInputs, load_config_inputs};
+use iflow_cli_action::cli::workflow::{StepInputs, input_env_var, load_step_inputs};
+use iflow_cli_action::error::Error;
+use iflow_cli_action::github::outputs::{
+    SummaryTarget, capture_step_summary, flush_local_summary, write_github_output,
+    write_step_summary,
+};
+use iflow_cli_action::github::summary::generate_summary_markdown;
+use iflow_cli_action::logging::{self, Level};
+use iflow_cli_action::runner::{Runner, register_secrets};
+use iflow_cli_action::telemetry;
+use serde_json::json;
+use std::collections::HashMap;
 use std::env;
 
-mod cli;
-mod executor;
-mod github;
-mod iflow;
-mod version_mgr;
-
-use cli::args::CliArgs;
-use executor::execute_precmd;
-use github::outputs::write_github_output;
-use iflow::acp_client::{AcpClientParams, communicate_with_iflow_cli_via_acp};
-use iflow::config::IFlowConfig;
-use version_mgr::{install_specific_versions, print_version_info};
-
-#[tokio::main]
-async fn main() -> Result<(), String> {
-    // Check if we're running in GitHub Actions environment
-    let is_github_actions = env::var("GITHUB_ACTIONS").is_ok();
+fn main() {
+    // GitHub Actions passes every input, so an empty one counts as not given
+    // and the config file or the built-in default applies
+    clear_empty_inputs();
 
     // Parse CLI arguments
-    let cli = CliArgs::parse();
-
-    // Validate the arguments
-    if let Err(e) = cli::validation::validate_args(
-        cli.prompt.as_ref(),
-        cli.api_key.as_ref(),
-        cli.settings_json.as_ref(),
-        cli.timeout,
-    ) {
-        eprintln!("Validation Error: {}", e);
-        std::process::exit(1);
+    let (mut cli, mut matches) = parse_args();
+
+    // `run --workflow` loads the step's inputs the way GitHub Actions passes
+    // them, as INPUT_* variables; flags given on the command line still win
+    let mut step = None;
+    if let Some(workflow) = cli.run_args().and_then(|run| run.workflow.clone()) {
+        let step_name = cli.run_args().and_then(|run| run.step.clone());
+        match load_step_inputs(&workflow, step_name.as_deref()) {
+            Ok(inputs) => {
+                set_inputs(&inputs.inputs, true);
+                (cli, matches) = parse_args();
+                step = Some(inputs);
+            }
+            Err(e) => fail(&e),
+        }
     }
 
-    // Install specific versions if requested
-    if let Err(e) = install_specific_versions(&cli.gh_version, &cli.iflow_version) {
-        eprintln!("Installation Error: {}", e);
-        std::process::exit(1);
+    // The config file fills in the inputs that were not given: its profile
+    // overrides its defaults, which override the built-in defaults
+    let mut config = None;
+    if let Some(config_file) = cli
+        .config_file
+        .clone()
+        .filter(|f| !f.as_os_str().is_empty())
+    {
+        let profile = cli.profile.as_deref().filter(|p| !p.is_empty());
+        match load_config_inputs(&config_file, profile) {
+            Ok(inputs) => {
+                set_inputs(&inputs.inputs, false);
+                (cli, matches) = parse_args();
+                config = Some(inputs);
+            }
+            Err(e) => fail(&e),
+        }
     }
 
-    // Print version information (after installing specific versions)
-    print_version_info();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the async runtime")
+        .block_on(run(cli, matches, step, config));
+}
 
-    // Configure iFlow settings
-    let iflow_config = IFlowConfig {
-        base_url: cli.base_url.clone(),
-        model: cli.model.clone(),
-    };
+/// Parses the arguments and INPUT_* variables, exiting on invalid ones; the
+/// matches tell which were given on the command line
+fn parse_args() -> (CliArgs, ArgMatches) {
+    let matches = CliArgs::command().get_matches();
+    let cli = CliArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    (cli, matches)
+}
 
-    if let Err(e) = iflow_config.configure(
-        cli.settings_json.as_ref(),
-        cli.api_key.as_deref().unwrap_or(""),
-        cli.settings_file_path.as_ref(),
-    ) {
-        eprintln!("Configuration Error: {}", e);
-        std::process::exit(1);
+/// Passes inputs the way GitHub Actions does, as INPUT_* variables; unless
+/// `overwrite` is set, inputs that were given keep their value
+fn set_inputs(inputs: &[(String, String)], overwrite: bool) {
+    for (name, value) in inputs {
+        let var = input_env_var(name);
+        if overwrite || env::var_os(&var).is_none() {
+            // SAFETY: no other thread has been started yet
+            unsafe { env::set_var(var, value) };
+        }
     }
+    clear_empty_inputs();
+}
 
-    // Execute pre-command if specified
-    if let Err(e) = execute_precmd(&cli.precmd, &cli.working_directory) {
-        eprintln!("Pre-command Error: {}", e);
-        std::process::exit(1);
+/// Removes empty INPUT_* variables, which clap would otherwise read as values
+fn clear_empty_inputs() {
+    let empty: Vec<_> = env::vars_os()
+        .filter(|(name, value)| name.to_string_lossy().starts_with("INPUT_") && value.is_empty())
+        .map(|(name, _)| name)
+        .collect();
+    for name in empty {
+        // SAFETY: no other thread has been started yet
+        unsafe { env::remove_var(name) };
     }
+}
 
-    // Run ACP client in GitHub Actions environment or when explicitly requested
-    if is_github_actions {
-        // Skip actual execution in dry-run mode
-        if cli.dry_run {
-            println!("DRY RUN: Would execute communicate_with_iflow_cli_via_acp()");
-            // In dry-run, write empty outputs with exit_code 0
-            let _ = write_github_output("result", "");
-            let _ = write_github_output("exit_code", "0");
-            return Ok(());
+async fn run(
+    cli: CliArgs,
+    matches: ArgMatches,
+    step: Option<StepInputs>,
+    config: Option<ConfigInputs>,
+) {
+    // Check if we're running in GitHub Actions environment; subcommands
+    // always run locally
+    let is_github_actions = env::var("GITHUB_ACTIONS").is_ok();
+    let local = cli.command.is_some() || !is_github_actions;
+
+    // Register secrets before anything is printed so they are masked everywhere;
+    // the format decides where the `::add-mask::` commands go
+    logging::set_format(cli.log_format);
+    register_secrets(&cli);
+
+    // Initialize logging and trace export before the first stage starts
+    telemetry::init(cli.debug, cli.log_format, cli.otlp_endpoint.as_deref());
+
+    if let Some(step) = &step {
+        logging::info(&format!(
+            "📄 Loaded {} input(s) from workflow step '{}'",
+            step.inputs.len(),
+            step.step
+        ));
+        for skipped in &step.skipped {
+            logging::warn(&format!(
+                "⚠️  Skipped input {}: the expression cannot be resolved locally",
+                skipped
+            ));
         }
+    }
 
-        // Run and capture summary (if any)
-        match communicate_with_iflow_cli_via_acp(AcpClientParams {
-            prompt: cli.prompt.as_ref().unwrap(),
-            base_url: &cli.base_url,
-            model: &cli.model,
-            working_directory: &cli.working_directory,
-            timeout: cli.timeout,
-            debug: cli.debug,
-        })
-        .await
-        {
-            Ok(maybe_summary) => {
-                if let Some(summary_content) = maybe_summary {
-                    // Write outputs: result (may be multiline) and exit_code=0
-                    if let Err(e) = write_github_output("result", &summary_content) {
-                        eprintln!("Warning: failed to write result output: {}", e);
-                    }
-                } else {
-                    // No summary produced
-                    let _ = write_github_output("result", "");
-                }
-
-                if let Err(e) = write_github_output("exit_code", "0") {
-                    eprintln!("Warning: failed to write exit_code output: {}", e);
-                }
-
-                return Ok(());
-            }
-            Err(err_msg) => {
-                // On error, write result with the error message and exit_code 1
-                let _ = write_github_output("result", &format!("ERROR: {}", err_msg));
-                let _ = write_github_output("exit_code", "1");
-                eprintln!("ACP client error: {}", err_msg);
-                std::process::exit(1);
-            }
+    // print-settings keeps stdout to the settings alone
+    if let (Some(config), Some(config_file)) = (&config, &cli.config_file)
+        && !matches!(cli.command, Some(Command::PrintSettings))
+    {
+        logging::info(&format!(
+            "📄 Loaded {} input(s) from config file '{}'{}",
+            config.inputs.len(),
+            config_file.display(),
+            config
+                .profile
+                .as_ref()
+                .map(|profile| format!(" (profile '{}')", profile))
+                .unwrap_or_default()
+        ));
+    }
+
+    // The other subcommands inspect the configuration without running the agent
+    let inspected = match &cli.command {
+        Some(Command::Validate) => Some(commands::validate(&cli)),
+        Some(Command::PrintSettings) => Some(commands::print_settings(&cli)),
+        Some(Command::Doctor) => Some(commands::doctor(&cli)),
+        Some(Command::Summarize(args)) => {
+            let matches = matches.subcommand_matches("summarize").unwrap_or(&matches);
+            Some(commands::summarize(&cli, args, matches))
+        }
+        Some(Command::Run(_)) | None => None,
+    };
+    if let Some(result) = inspected {
+        if let Err(err) = result {
+            fail(&err);
         }
+        telemetry::shutdown();
+        return;
+    }
+
+    // A local run prints the summary or writes it to a file at the end
+    if local {
+        capture_step_summary(
+            cli.run_args()
+                .map(|run| run.summary.clone())
+                .unwrap_or(SummaryTarget::Stdout),
+        );
+    }
+
+    // Cancel the session on SIGTERM (a cancelled workflow) or SIGINT; outside a
+    // session the run stops right away with a cancelled summary
+    let cancelled_summary = HashMap::from([
+        ("model", json!(cli.model)),
+        ("baseURL", json!(cli.base_url)),
+        ("timeout", json!(cli.timeout)),
+        ("workingDir", json!(cli.working_directory)),
+        ("prompt", json!(cli.prompt.as_deref().unwrap_or_default())),
+    ]);
+    cancel::listen(move |err| {
+        let mut config = cancelled_summary.clone();
+        config.insert("cancelled", json!(err.to_string()));
+        let _ = write_step_summary(&generate_summary_markdown(
+            &err.to_string(),
+            err.exit_code(),
+            &config,
+        ));
+        let _ = write_github_output("result", &format!("ERROR: {}", err));
+        fail(&err);
+    });
+
+    let runner = Runner::builder(cli).write_outputs(!local).build();
+    if let Err(err) = runner.run().await {
+        fail(&err);
     }
 
-    // Print the parsed arguments for verification
-    println!("Parsed arguments:");
-    println!("  prompt: {:?}", cli.prompt);
-    println!("  api_key: {:?}", cli.api_key);
-    println!("  settings_json: {:?}", cli.settings_json);
-    println!("  base_url: {}", cli.base_url);
-    println!("  model: {}", cli.model);
-    println!("  working_directory: {}", cli.working_directory);
-    println!("  timeout: {}", cli.timeout);
-    println!("  precmd: {:?}", cli.precmd);
-    println!("  gh_version: {:?}", cli.gh_version);
-    println!("  iflow_version: {:?}", cli.iflow_version);
-    println!("  dry_run: {}", cli.dry_run);
-
-    Ok(())
+    if !local && let Err(e) = write_github_output("exit_code", "0") {
+        logging::warn(&format!("Warning: failed to write exit_code output: {}", e));
+    }
+    if let Err(e) = flush_local_summary() {
+        logging::warn(&format!("⚠️  Warning: {}", e));
+    }
+    telemetry::shutdown();
+}
+
+/// Reports an error, writes the `exit_code` and `error_kind` outputs and exits with the
+/// exit code of the error's category
+fn fail(err: &Error) -> ! {
+    logging::event(
+        Level::Error,
+        "failure",
+        &err.to_string(),
+        json!({ "kind": err.kind(), "exitCode": err.exit_code() }),
+    );
+    let _ = write_github_output("exit_code", &err.exit_code().to_string());
+    let _ = write_github_output("error_kind", err.kind());
+    if let Err(e) = flush_local_summary() {
+        logging::warn(&format!("⚠️  Warning: {}", e));
+    }
+    if let Error::Cancelled(_) = err {
+        cancel::cleanup();
+    }
+    telemetry::shutdown();
+    std::process::exit(err.exit_code());
 }
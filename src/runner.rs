@@ -0,0 +1,404 @@
+use crate::cancel;
+use crate::cli::args::CliArgs;
+use crate::cli::validation::validate_args;
+use crate::error::{Error, Result};
+use crate::executor::execute_precmd;
+use crate::github::annotations::{AnnotationLevel, annotate};
+use crate::github::outputs::{write_github_output, write_step_summary};
+use crate::github::review::{Finding, ReviewMode, publish_findings, review_prompt};
+use crate::github::summary::generate_triage_markdown;
+use crate::github::triage::{Triage, TriageDecision};
+use crate::iflow::acp_client::{
+    AcpClientParams, MessageHandler, SessionAttempt, communicate_with_iflow_cli_via_acp,
+};
+use crate::iflow::config::IFlowConfig;
+use crate::iflow::metrics::SessionMetrics;
+use crate::iflow::stop_reason::StopReason;
+use crate::logging;
+use crate::secrets;
+use crate::telemetry;
+use crate::version_mgr::{
+    InstallParams, check_iflow_compatibility, environment_report, install_specific_versions,
+    print_version_info,
+};
+pub use iflow_cli_sdk_rust::Message;
+use serde_json::json;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// What a successful run produced; the fields mirror the action's outputs
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    /// Generated summary (the `result` output), empty when there was nothing
+    /// to summarize or in a dry run
+    pub result: String,
+    /// Everything the assistant wrote during the session
+    pub response: String,
+    /// Model that produced the result, after any fallbacks
+    pub model_used: String,
+    /// Time spent in sessions, including retries and fallbacks
+    pub duration: Duration,
+    pub metrics: SessionMetrics,
+    /// Answer validated against the output schema
+    pub structured_result: Option<serde_json::Value>,
+    /// Why the agent stopped working on the last prompt
+    pub stop_reason: Option<StopReason>,
+    /// Labels applied in triage mode
+    pub triage: Option<TriageDecision>,
+    /// Review findings that were published, `None` when no review was published
+    pub findings: Option<Vec<Finding>>,
+    /// Failed attempts before the successful one
+    pub attempts: Vec<SessionAttempt>,
+    /// Environment report (the `environment` output)
+    pub environment: serde_json::Value,
+}
+
+/// Runs the whole action: validation, installation, configuration, the
+/// pre-command, the agent session with retries and model fallback, and the
+/// triage and review follow-ups. Errors are returned instead of exiting.
+///
+/// ```no_run
+/// # async fn example(args: iflow_cli_action::cli::args::CliArgs) {
+/// use iflow_cli_action::runner::Runner;
+///
+/// let report = Runner::builder(args)
+///     .prompt("Summarize the open TODOs")
+///     .on_message(|message| println!("{:?}", message))
+///     .build()
+///     .run()
+///     .await;
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Runner {
+    config: CliArgs,
+    on_message: Option<MessageHandler>,
+    write_outputs: bool,
+}
+
+/// Builder of a [`Runner`]
+#[derive(Debug)]
+pub struct RunnerBuilder {
+    config: CliArgs,
+    on_message: Option<MessageHandler>,
+    write_outputs: bool,
+}
+
+impl RunnerBuilder {
+    /// Replaces the prompt of the configuration
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.config.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Calls `handler` with every message received from iFlow
+    pub fn on_message(mut self, handler: impl Fn(&Message) + 'static) -> Self {
+        self.on_message = Some(MessageHandler::new(handler));
+        self
+    }
+
+    /// Runs everything except the agent session
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
+    /// Writes the GitHub Actions outputs (`result`, `model_used`, ...) as the
+    /// run progresses. Off by default; step summaries are always written in
+    /// GitHub Actions.
+    pub fn write_outputs(mut self, write_outputs: bool) -> Self {
+        self.write_outputs = write_outputs;
+        self
+    }
+
+    pub fn build(self) -> Runner {
+        Runner {
+            config: self.config,
+            on_message: self.on_message,
+            write_outputs: self.write_outputs,
+        }
+    }
+}
+
+impl Runner {
+    /// Starts building a runner for the given inputs
+    pub fn builder(config: CliArgs) -> RunnerBuilder {
+        RunnerBuilder {
+            config,
+            on_message: None,
+            write_outputs: false,
+        }
+    }
+
+    /// The inputs of the run
+    pub fn config(&self) -> &CliArgs {
+        &self.config
+    }
+
+    /// Writes a GitHub Actions output when enabled
+    fn output(&self, key: &str, value: &str) -> Result<()> {
+        if self.write_outputs {
+            write_github_output(key, value)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs the action and reports what it produced
+    pub async fn run(&self) -> Result<RunReport> {
+        let cli = &self.config;
+        register_secrets(cli);
+
+        // Validate the arguments
+        telemetry::stage("validate").in_scope(|| validate_args(cli))?;
+
+        // Install specific versions if requested
+        let install_params = InstallParams {
+            gh_version: cli.gh_version.as_deref(),
+            iflow_version: cli.iflow_version.as_deref(),
+            cache_dir: cli.cache_dir.as_deref(),
+            npm_registry: cli.npm_registry.as_deref(),
+            gh_api_url: cli.gh_api_url.as_deref(),
+            gh_tarball: cli.gh_tarball.as_deref(),
+            iflow_tarball: cli.iflow_tarball.as_deref(),
+            npm_cache: cli.npm_cache.as_deref(),
+        };
+        telemetry::stage("install").in_scope(|| install_specific_versions(&install_params))?;
+
+        // Print version information (after installing specific versions)
+        print_version_info();
+
+        // Expose the environment report before anything else can fail
+        let environment = environment_report(&cli.model, &cli.base_url);
+        if let Err(e) = self.output("environment", &environment.to_string()) {
+            logging::warn(&format!(
+                "Warning: failed to write environment output: {}",
+                e
+            ));
+        }
+
+        // Fail fast before connecting when the iFlow CLI is too old for the SDK
+        check_iflow_compatibility()?;
+
+        // Configure iFlow settings
+        let mut iflow_config = cli.iflow_config();
+
+        // Keep the previous settings so a cancelled run can put them back
+        cancel::restore_settings_on_cancel(IFlowConfig::backup_settings(
+            cli.settings_file_path.as_ref(),
+        )?);
+        telemetry::stage("configure").in_scope(|| {
+            iflow_config.configure(
+                cli.settings_json.as_ref(),
+                cli.api_key.as_deref().unwrap_or(""),
+                cli.settings_file_path.as_ref(),
+            )
+        })?;
+
+        // Execute pre-command if specified
+        telemetry::stage("precmd")
+            .in_scope(|| execute_precmd(&cli.precmd, &cli.working_directory))?;
+
+        // Skip actual execution in dry-run mode
+        if cli.dry_run {
+            logging::info("DRY RUN: Would execute communicate_with_iflow_cli_via_acp()");
+            logging::info(&format!("DRY RUN: Models: {}", cli.models().join(", ")));
+            // In dry-run, write empty outputs
+            let _ = self.output("result", "");
+            let _ = self.output("model_used", &cli.model);
+            let _ = self.output("duration_ms", "0");
+            return Ok(RunReport {
+                model_used: cli.model.clone(),
+                environment,
+                ..RunReport::default()
+            });
+        }
+
+        // Run and capture summary (if any), falling back to the next model on
+        // model unavailability or connection failures
+        let models = cli.models();
+        let retry_policy = cli.retry_policy();
+        let model_prices = cli.model_prices();
+        let mut output_schema = cli.output_schema();
+        let mut prompt = match cli.review_mode {
+            ReviewMode::Off => cli.prompt.clone().unwrap_or_default(),
+            _ => review_prompt(cli.prompt.as_deref().unwrap_or_default()),
+        };
+
+        // Triage fetches the issue and labels and asks for labels from that list
+        let triage = if cli.triage {
+            let (triage, schema) = telemetry::stage("triage").in_scope(|| {
+                let triage =
+                    Triage::prepare(cli.github_token.as_deref().unwrap_or(""), cli.issue_number)?;
+                let schema = triage.schema()?;
+                Ok::<_, Error>((triage, schema))
+            })?;
+            logging::info(&format!(
+                "🏷️  Triaging issue #{} with {} available labels",
+                triage.issue.number,
+                triage.labels.len()
+            ));
+            prompt = triage.prompt(cli.prompt.as_deref());
+            output_schema = Some(schema);
+            Some(triage)
+        } else {
+            None
+        };
+        if let Some(schema) = &output_schema {
+            prompt.push_str(&schema.instructions());
+        }
+        let started = Instant::now();
+        let params = AcpClientParams {
+            prompt: &prompt,
+            base_url: &cli.base_url,
+            models: &models,
+            working_directory: &cli.working_directory,
+            timeout: cli.timeout,
+            debug: cli.debug,
+            fallback_error_codes: &cli.fallback_error_codes,
+            retry: &retry_policy,
+            environment: &environment,
+            model_prices: &model_prices,
+            budget: cli.budget(),
+            output_schema: output_schema.as_ref(),
+            schema_retries: cli.output_schema_retries,
+            watchdog: cli.watchdog(),
+            fail_on_stop_reason: &cli.fail_on_stop_reason,
+            on_message: self.on_message.as_ref(),
+        };
+        let mut switch_model =
+            |model: &str| iflow_config.set_model(model, cli.settings_file_path.as_ref());
+        let (outcome, attempts) =
+            communicate_with_iflow_cli_via_acp(params, &mut switch_model).await;
+
+        let duration = started.elapsed();
+        let _ = self.output("model_used", &iflow_config.model);
+        let _ = self.output("duration_ms", &duration.as_millis().to_string());
+        let output = match outcome {
+            Ok(output) => output,
+            Err(err) => {
+                // On error, write result with the error message before failing
+                let _ = self.output("result", &format!("ERROR: {}", err));
+                if let Error::StopReason(stop_reason) = &err {
+                    let _ = self.output("stop_reason", stop_reason);
+                }
+                return Err(err);
+            }
+        };
+
+        // Token usage is estimated from the prompts and the assistant output
+        let tokens = |count: Option<u64>| count.map(|c| c.to_string()).unwrap_or_default();
+        let _ = self.output("input_tokens", &tokens(output.metrics.input_tokens));
+        let _ = self.output("output_tokens", &tokens(output.metrics.output_tokens));
+
+        let stop_reason = output.stop_reason.map(|r| r.as_str()).unwrap_or_default();
+        let _ = self.output("stop_reason", stop_reason);
+
+        if let Some(structured_result) = &output.structured_result {
+            let _ = self.output("structured_result", &structured_result.to_string());
+        }
+
+        // Apply the labels chosen by the model
+        let decision = match &triage {
+            Some(triage) => {
+                let applied = output
+                    .structured_result
+                    .as_ref()
+                    .ok_or_else(|| Error::Schema("the model gave no triage answer".to_string()))
+                    .and_then(|answer| triage.apply(answer));
+                let decision = match applied {
+                    Ok(decision) => decision,
+                    Err(e) => {
+                        let _ = self.output("result", &format!("ERROR: {}", e));
+                        return Err(e);
+                    }
+                };
+                logging::info(&format!(
+                    "✅ Applied labels to issue #{}: {}",
+                    triage.issue.number,
+                    decision.labels.join(", ")
+                ));
+                let _ = self.output("applied_labels", &json!(decision.labels).to_string());
+                let _ = self.output("triage_rationale", &decision.rationale);
+                if let Err(e) = write_step_summary(&generate_triage_markdown(
+                    triage.issue.number,
+                    &decision.labels,
+                    &decision.rationale,
+                )) {
+                    logging::warn(&format!("⚠️  Warning: Failed to write step summary: {}", e));
+                }
+                Some(decision)
+            }
+            None => None,
+        };
+
+        // Publish review findings; a failed review does not fail the run
+        let findings = if cli.review_mode != ReviewMode::Off {
+            let published = telemetry::stage("review").in_scope(|| {
+                publish_findings(
+                    cli.review_mode,
+                    &output.response,
+                    Path::new(&cli.working_directory),
+                    cli.github_token.as_deref(),
+                )
+            });
+            match published {
+                Ok(findings) => {
+                    let entries: Vec<_> = findings.iter().map(Finding::to_json).collect();
+                    let _ = self.output("findings", &json!(entries).to_string());
+                    Some(findings)
+                }
+                Err(e) => {
+                    logging::warn(&format!("⚠️  Warning: failed to publish review: {}", e));
+                    annotate(
+                        AnnotationLevel::Warning,
+                        &format!("Failed to publish review: {}", e),
+                        None,
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Write outputs: result (may be multiline); no summary leaves it empty
+        let result = output.summary.unwrap_or_default();
+        if let Err(e) = self.output("result", &result) {
+            logging::warn(&format!("Warning: failed to write result output: {}", e));
+        }
+
+        Ok(RunReport {
+            result,
+            response: output.response,
+            model_used: iflow_config.model,
+            duration,
+            metrics: output.metrics,
+            structured_result: output.structured_result,
+            stop_reason: output.stop_reason,
+            triage: decision,
+            findings,
+            attempts,
+            environment,
+        })
+    }
+}
+
+/// Registers the API keys, secrets inside settings_json and token environment
+/// variables for masking
+pub fn register_secrets(cli: &CliArgs) {
+    for key in [&cli.api_key, &cli.search_api_key, &cli.github_token]
+        .into_iter()
+        .flatten()
+    {
+        secrets::register_secret(key);
+    }
+
+    if let Some(settings_json) = &cli.settings_json
+        && let Ok(settings) = serde_json::from_str::<serde_json::Value>(settings_json)
+    {
+        secrets::register_settings_secrets(&settings);
+    }
+
+    secrets::register_env_secrets();
+}
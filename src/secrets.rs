@@ -0,0 +1,215 @@
+use crate::logging;
+use serde_json::Value;
+use std::io::Write;
+use std::sync::{LazyLock, Mutex};
+
+/// Placeholder that replaces every registered secret in console output
+pub const REDACTED: &str = "***";
+
+/// Secrets shorter than this are not redacted to avoid mangling ordinary text
+const MIN_SECRET_LEN: usize = 4;
+
+/// Settings keys whose values are always treated as secrets
+const SECRET_KEYS: [&str; 6] = [
+    "apikey",
+    "searchapikey",
+    "token",
+    "secret",
+    "password",
+    "authorization",
+];
+
+/// Environment variables holding tokens that must never appear in logs
+const SECRET_ENV_VARS: [&str; 3] = ["GITHUB_TOKEN", "GH_TOKEN", "IFLOW_API_KEY"];
+
+static SECRETS: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Registers a secret value so it is masked in GitHub Actions logs and redacted
+/// from all console output, summaries and outputs produced by this action.
+pub fn register_secret(value: &str) {
+    let value = value.trim();
+    if value.chars().count() < MIN_SECRET_LEN {
+        return;
+    }
+
+    let Ok(mut secrets) = SECRETS.lock() else {
+        return;
+    };
+    if secrets.iter().any(|s| s == value) {
+        return;
+    }
+
+    // The runner masks line by line, so register each line of multiline values
+    if std::env::var("GITHUB_ACTIONS").is_ok() {
+        for line in value.lines().map(str::trim) {
+            if line.chars().count() >= MIN_SECRET_LEN {
+                logging::add_mask(line);
+            }
+        }
+    }
+
+    secrets.push(value.to_string());
+    // Redact longer secrets first so a secret containing another is fully hidden
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+}
+
+/// Registers every secret-like value found in an iFlow settings document:
+/// credential keys (`apiKey`, `searchApiKey`, tokens, passwords) at any depth
+/// and all MCP server `env` values.
+pub fn register_settings_secrets(settings: &Value) {
+    collect_settings_secrets(settings, false);
+}
+
+fn collect_settings_secrets(value: &Value, in_env: bool) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                if let Value::String(s) = child
+                    && (in_env || is_secret_key(key))
+                {
+                    register_secret(s);
+                } else {
+                    collect_settings_secrets(child, key == "env" || key == "headers");
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_settings_secrets(item, false);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns a copy of an iFlow settings document with the values that
+/// [`register_settings_secrets`] treats as secrets replaced by [`REDACTED`];
+/// empty values are kept so missing credentials remain visible
+pub fn redact_settings(settings: &Value) -> Value {
+    redact_settings_value(settings, false)
+}
+
+fn redact_settings_value(value: &Value, in_env: bool) -> Value {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, child)| {
+                let child = match child {
+                    Value::String(s) if !s.is_empty() && (in_env || is_secret_key(key)) => {
+                        Value::String(REDACTED.to_string())
+                    }
+                    _ => redact_settings_value(child, key == "env" || key == "headers"),
+                };
+                (key.clone(), child)
+            })
+            .collect(),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| redact_settings_value(item, false))
+                .collect(),
+        ),
+        Value::String(s) => Value::String(redact(s)),
+        other => other.clone(),
+    }
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase().replace(['_', '-'], "");
+    SECRET_KEYS.iter().any(|k| key.ends_with(k))
+}
+
+/// Registers token-bearing environment variables (e.g. `GITHUB_TOKEN`)
+pub fn register_env_secrets() {
+    for name in SECRET_ENV_VARS {
+        if let Ok(value) = std::env::var(name) {
+            register_secret(&value);
+        }
+    }
+}
+
+/// Replaces every registered secret in `text` with [`REDACTED`]
+pub fn redact(text: &str) -> String {
+    let Ok(secrets) = SECRETS.lock() else {
+        return text.to_string();
+    };
+
+    let mut redacted = text.to_string();
+    for secret in secrets.iter() {
+        if redacted.contains(secret.as_str()) {
+            redacted = redacted.replace(secret.as_str(), REDACTED);
+        }
+    }
+    redacted
+}
+
+/// Redacts text that arrives in chunks, such as streamed agent output: the end
+/// of a chunk that could be the start of a secret is held back until the next
+/// chunk shows whether it is one
+#[derive(Debug, Default)]
+pub struct StreamRedactor {
+    pending: String,
+}
+
+impl StreamRedactor {
+    pub const fn new() -> Self {
+        Self {
+            pending: String::new(),
+        }
+    }
+
+    /// Adds a chunk and returns the redacted text that is safe to print
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let redacted = redact(&self.pending);
+        let held = partial_secret_len(&redacted);
+        self.pending = redacted[redacted.len() - held..].to_string();
+        redacted[..redacted.len() - held].to_string()
+    }
+
+    /// Returns the text held back, redacted, once no more chunks follow
+    pub fn finish(&mut self) -> String {
+        redact(&std::mem::take(&mut self.pending))
+    }
+}
+
+/// Length of the longest end of `text` that is the start of a registered secret
+fn partial_secret_len(text: &str) -> usize {
+    let Ok(secrets) = SECRETS.lock() else {
+        return 0;
+    };
+    secrets
+        .iter()
+        .filter_map(|secret| {
+            (1..secret.len())
+                .rev()
+                .filter(|len| secret.is_char_boundary(*len))
+                .find(|len| text.ends_with(&secret[..*len]))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Writer that redacts registered secrets before forwarding to the inner writer.
+/// Used as the `tracing_subscriber` writer so debug logs never leak secrets.
+pub struct RedactingWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> RedactingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{Error, Result};
+use crate::logging::{self, JsonLayer, LogFormat};
+use crate::secrets::{RedactingWriter, redact};
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::sync::{Mutex, OnceLock};
+use tracing::{Level, Span};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::prelude::*;
+
+/// Span target prefix of this crate; only these spans are exported
+const SPAN_TARGET: &str = "iflow_cli_action";
+
+/// Tracer provider, set when OTLP export is enabled
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Root span of the run; it ends on [`shutdown`] so failed runs are exported too
+static ROOT: Mutex<Option<Span>> = Mutex::new(None);
+
+/// Returns the OTLP/HTTP traces URL for a collector endpoint such as
+/// `http://localhost:4318`
+pub fn traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// Repository, workflow and run identifiers from the GitHub Actions environment
+fn github_attributes() -> Vec<KeyValue> {
+    [
+        ("github.repository", "GITHUB_REPOSITORY"),
+        ("github.workflow", "GITHUB_WORKFLOW"),
+        ("github.run_id", "GITHUB_RUN_ID"),
+        ("github.run_attempt", "GITHUB_RUN_ATTEMPT"),
+        ("github.job", "GITHUB_JOB"),
+        ("github.sha", "GITHUB_SHA"),
+    ]
+    .into_iter()
+    .filter_map(|(key, var)| {
+        std::env::var(var)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| KeyValue::new(key, v))
+    })
+    .collect()
+}
+
+fn build_provider(endpoint: &str) -> Result<SdkTracerProvider> {
+    let endpoint = traces_endpoint(endpoint);
+
+    // The blocking HTTP client must not be created on an async runtime thread
+    std::thread::spawn(move || {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_protocol(Protocol::HttpBinary)
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| Error::Configuration(format!("failed to create OTLP exporter: {}", e)))?;
+        let resource = Resource::builder()
+            .with_service_name("iflow-cli-action")
+            .with_attributes([KeyValue::new("service.version", env!("CARGO_PKG_VERSION"))])
+            .with_attributes(github_attributes())
+            .build();
+
+        Ok(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build())
+    })
+    .join()
+    .map_err(|_| Error::Configuration("failed to create OTLP exporter".to_string()))?
+}
+
+/// Initializes logging in `log_format` and, when `otlp_endpoint` is set, OTLP
+/// trace export. Telemetry never fails the run; export problems are reported
+/// as warnings.
+pub fn init(debug: bool, log_format: LogFormat, otlp_endpoint: Option<&str>) {
+    logging::set_format(log_format);
+    let level = if debug || std::env::var("ACTIONS_STEP_DEBUG").is_ok() {
+        Level::DEBUG
+    } else {
+        Level::INFO
+    };
+
+    let provider = match otlp_endpoint.filter(|e| !e.is_empty()).map(build_provider) {
+        Some(Ok(provider)) => Some(provider),
+        Some(Err(e)) => {
+            logging::warn(&format!("⚠️  Warning: OTLP export disabled: {}", e));
+            None
+        }
+        None => None,
+    };
+
+    // Spans go to the exporter only; logs keep their plain format without span context
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("iflow-cli-action"))
+            .with_filter(filter_fn(|meta| {
+                meta.is_span() && meta.target().starts_with(SPAN_TARGET)
+            }))
+    });
+    let events = filter_fn(move |meta| meta.is_event() && *meta.level() <= level);
+    let fmt_layer = (log_format != LogFormat::Json).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(log_format == LogFormat::Pretty)
+            .with_writer(|| RedactingWriter::new(std::io::stdout()))
+            .with_filter(events.clone())
+    });
+    let json_layer = (log_format == LogFormat::Json).then(|| JsonLayer.with_filter(events));
+    let _ = tracing_subscriber::registry()
+        .with(otel_layer)
+        .with(fmt_layer)
+        .with(json_layer)
+        .try_init();
+
+    if let Some(provider) = provider {
+        let _ = PROVIDER.set(provider);
+    }
+    if let Ok(mut root) = ROOT.lock() {
+        *root = Some(tracing::info_span!("run"));
+    }
+}
+
+/// Returns the root span of the run
+pub fn root() -> Span {
+    ROOT.lock()
+        .ok()
+        .and_then(|root| root.clone())
+        .unwrap_or_else(Span::none)
+}
+
+/// Starts a span for a stage of the run such as `validate` or `install`
+pub fn stage(name: &str) -> Span {
+    tracing::info_span!(parent: &root(), "stage", otel.name = name)
+}
+
+/// Ends the root span and flushes pending spans to the collector
+pub fn shutdown() {
+    if let Ok(mut root) = ROOT.lock() {
+        root.take();
+    }
+
+    if let Some(provider) = PROVIDER.get() {
+        let provider = provider.clone();
+        let flushed = std::thread::spawn(move || provider.shutdown()).join();
+        if let Ok(Err(e)) = flushed {
+            logging::warn(&format!("⚠️  Warning: failed to export traces: {}", e));
+        }
+    }
+}
+
+/// Spans of an agent session: one per prompt turn and one per tool call.
+/// Like the metrics, a tool call span ends with the next session event.
+#[derive(Debug)]
+pub struct SessionSpans {
+    session: Span,
+    turn: Option<(u32, Span)>,
+    tool_call: Option<(String, Span)>,
+}
+
+impl SessionSpans {
+    pub fn new(session: Span) -> Self {
+        SessionSpans {
+            session,
+            turn: None,
+            tool_call: None,
+        }
+    }
+
+    /// Records a session event, ending a running tool call
+    pub fn event(&mut self) {
+        self.tool_call = None;
+    }
+
+    /// Starts the span of turn `number` unless it is already running
+    pub fn turn(&mut self, number: u32) {
+        if self
+            .turn
+            .as_ref()
+            .is_some_and(|(current, _)| *current == number)
+        {
+            return;
+        }
+        self.tool_call = None;
+        let span = tracing::info_span!(parent: &self.session, "turn", turn = number);
+        self.turn = Some((number, span));
+    }
+
+    /// Starts the span of a tool call; repeated events for the same call keep it running
+    pub fn tool_call(&mut self, id: &str, name: &str) {
+        if self
+            .tool_call
+            .as_ref()
+            .is_some_and(|(running, _)| running == id)
+        {
+            return;
+        }
+        let parent = self.turn.as_ref().map_or(&self.session, |(_, span)| span);
+        let span = tracing::info_span!(parent: parent, "tool_call", tool.id = id, tool.name = %redact(name));
+        self.tool_call = Some((id.to_string(), span));
+    }
+}
@@ -1,18 +1,27 @@
+use crate::error::{Error, Result};
+use crate::github::annotations::{AnnotationLevel, annotation};
+use crate::logging::{self, workflow_command};
+use semver::{Version, VersionReq};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// Gets the version of a command by running it with --version flag
-pub fn get_command_version(command: &str) -> Result<String, String> {
+pub fn get_command_version(command: &str) -> Result<String> {
     let output = Command::new(command)
         .arg("--version")
         .output()
-        .map_err(|e| format!("failed to execute {} --version: {}", command, e))?;
+        .map_err(|e| Error::Install(format!("failed to execute {} --version: {}", command, e)))?;
 
     if output.status.success() {
         let version = String::from_utf8_lossy(&output.stdout);
         Ok(version.trim().to_string())
     } else {
         let error = String::from_utf8_lossy(&output.stderr);
-        Err(format!("failed to get {} version: {}", command, error))
+        Err(Error::Install(format!(
+            "failed to get {} version: {}",
+            command, error
+        )))
     }
 }
 
@@ -25,16 +34,24 @@ pub fn print_version_info() {
     match get_command_version("iflow") {
         Ok(version) => {
             if is_github_actions {
-                println!("::notice::iFlow CLI version: {}", version);
+                workflow_command(&annotation(
+                    AnnotationLevel::Notice,
+                    &format!("iFlow CLI version: {}", version),
+                    None,
+                ));
             } else {
-                println!("INFO: iFlow CLI version: {}", version);
+                logging::info(&format!("INFO: iFlow CLI version: {}", version));
             }
         }
         Err(e) => {
             if is_github_actions {
-                println!("::warning::Failed to get iFlow CLI version: {}", e);
+                workflow_command(&annotation(
+                    AnnotationLevel::Warning,
+                    &format!("Failed to get iFlow CLI version: {}", e),
+                    None,
+                ));
             } else {
-                eprintln!("WARNING: Failed to get iFlow CLI version: {}", e);
+                logging::warn(&format!("WARNING: Failed to get iFlow CLI version: {}", e));
             }
         }
     }
@@ -43,82 +60,666 @@ pub fn print_version_info() {
     match get_command_version("gh") {
         Ok(version) => {
             if is_github_actions {
-                println!("::notice::GitHub CLI version: {}", version);
+                workflow_command(&annotation(
+                    AnnotationLevel::Notice,
+                    &format!("GitHub CLI version: {}", version),
+                    None,
+                ));
             } else {
-                println!("INFO: GitHub CLI version: {}", version);
+                logging::info(&format!("INFO: GitHub CLI version: {}", version));
             }
         }
         Err(e) => {
             if is_github_actions {
-                println!("::warning::Failed to get GitHub CLI version: {}", e);
+                workflow_command(&annotation(
+                    AnnotationLevel::Warning,
+                    &format!("Failed to get GitHub CLI version: {}", e),
+                    None,
+                ));
             } else {
-                eprintln!("WARNING: Failed to get GitHub CLI version: {}", e);
+                logging::warn(&format!("WARNING: Failed to get GitHub CLI version: {}", e));
             }
         }
     }
 }
 
-/// Installs specific versions of GitHub CLI and iFlow CLI if requested
-pub fn install_specific_versions(
-    gh_version: &Option<String>,
-    iflow_version: &Option<String>,
-) -> Result<(), String> {
-    // Install specific GitHub CLI version if requested
-    if let Some(gh_version) = gh_version
-        && !gh_version.is_empty()
-    {
-        println!("Installing GitHub CLI version: {}", gh_version);
+/// Returns the host (and port) of a URL, dropping scheme, credentials and path
+pub fn url_host(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    (!host.is_empty()).then(|| host.to_string())
+}
 
-        let install_cmd = format!(
-            "curl -fsSL https://github.com/cli/cli/releases/download/v{0}/gh_{0}_linux_amd64.tar.gz | tar xz && cp gh_{0}_linux_amd64/bin/gh /usr/local/bin/ && rm -rf gh_{0}_linux_amd64",
-            gh_version
-        );
-
-        let output = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(&install_cmd)
-            .output()
-            .map_err(|e| format!("failed to execute GitHub CLI installation command: {}", e))?;
-
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(format!(
-                "failed to install GitHub CLI version {}: {}",
-                gh_version, error
+/// Collects the versions and platform of this run as a JSON object, used to
+/// correlate regressions across workflow runs. Undetectable versions are `null`.
+pub fn environment_report(model: &str, base_url: &str) -> serde_json::Value {
+    serde_json::json!({
+        "action_version": env!("CARGO_PKG_VERSION"),
+        "iflow_version": installed_version("iflow"),
+        "gh_version": installed_version("gh"),
+        "node_version": installed_version("node"),
+        "sdk_version": iflow_cli_sdk_rust::VERSION,
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "model": model,
+        "base_url_host": url_host(base_url),
+    })
+}
+
+/// Directory that installed binaries are placed in; it precedes the
+/// pre-installed tools in PATH
+const INSTALL_BIN_DIR: &str = "/usr/local/bin";
+
+/// Default npm registry used to resolve and install iFlow CLI versions
+pub const DEFAULT_NPM_REGISTRY: &str = "https://registry.npmjs.org";
+
+/// Default GitHub API used to resolve GitHub CLI release versions
+pub const DEFAULT_GH_API_URL: &str = "https://api.github.com";
+
+/// Oldest iFlow CLI release supported by the bundled iflow-cli-sdk-rust
+pub const MIN_IFLOW_VERSION: &str = "0.2.4";
+
+/// Parameters for installing specific versions of GitHub CLI and iFlow CLI
+#[derive(Debug)]
+pub struct InstallParams<'a> {
+    pub gh_version: Option<&'a str>,
+    pub iflow_version: Option<&'a str>,
+    /// Cache directory for downloaded releases, keyed by version
+    pub cache_dir: Option<&'a str>,
+    /// npm registry or mirror used to resolve and install iFlow CLI
+    pub npm_registry: Option<&'a str>,
+    /// GitHub API (or mirror) used to resolve GitHub CLI releases
+    pub gh_api_url: Option<&'a str>,
+    /// Local GitHub CLI release tarball, installed without network access
+    pub gh_tarball: Option<&'a str>,
+    /// Local iFlow CLI package tarball (`npm pack` output)
+    pub iflow_tarball: Option<&'a str>,
+    /// Pre-populated npm cache used to install iFlow CLI offline
+    pub npm_cache: Option<&'a str>,
+}
+
+/// A requested tool version: `latest`, an exact version or a semver range
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionSpec {
+    Latest,
+    Exact(Version),
+    Range(VersionReq),
+}
+
+impl VersionSpec {
+    /// Parses `latest`, `0.2.4`, `v2.76.2`, `^0.3` or `>=0.2.4`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        if spec.eq_ignore_ascii_case("latest") {
+            return Ok(VersionSpec::Latest);
+        }
+        if let Ok(version) = Version::parse(spec.trim_start_matches('v')) {
+            return Ok(VersionSpec::Exact(version));
+        }
+        VersionReq::parse(spec)
+            .map(VersionSpec::Range)
+            .map_err(|e| Error::Validation(format!("invalid version '{}': {}", spec, e)))
+    }
+
+    /// Whether an installed version satisfies this spec without resolving it;
+    /// `latest` always needs to be resolved against the registry
+    pub fn matches(&self, version: &str) -> bool {
+        let Ok(version) = Version::parse(version) else {
+            return false;
+        };
+        match self {
+            VersionSpec::Latest => false,
+            VersionSpec::Exact(exact) => *exact == version,
+            VersionSpec::Range(req) => req.matches(&version),
+        }
+    }
+}
+
+impl std::fmt::Display for VersionSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionSpec::Latest => write!(f, "latest"),
+            VersionSpec::Exact(version) => write!(f, "{}", version),
+            VersionSpec::Range(req) => write!(f, "{}", req),
+        }
+    }
+}
+
+/// Picks the version satisfying `spec` from the `available` versions, using
+/// `latest` (the registry's latest tag) when the spec is `latest`
+pub fn select_version(
+    spec: &VersionSpec,
+    available: &[String],
+    latest: Option<&str>,
+) -> Option<String> {
+    let mut versions: Vec<Version> = available
+        .iter()
+        .filter_map(|v| Version::parse(v.trim_start_matches('v')).ok())
+        .collect();
+    versions.sort();
+
+    match spec {
+        VersionSpec::Exact(version) => Some(version.to_string()),
+        VersionSpec::Latest => latest.map(str::to_string).or_else(|| {
+            versions
+                .iter()
+                .rev()
+                .find(|v| v.pre.is_empty())
+                .map(Version::to_string)
+        }),
+        VersionSpec::Range(req) => versions
+            .iter()
+            .rev()
+            .find(|v| req.matches(v))
+            .map(Version::to_string),
+    }
+}
+
+/// Fetches and parses a JSON document
+fn fetch_json(url: &str) -> Result<serde_json::Value> {
+    let stdout = run_install_command(
+        Command::new("curl").arg("-fsSL").arg(url),
+        &format!("request to {}", url),
+    )?;
+    serde_json::from_slice(&stdout)
+        .map_err(|e| Error::Install(format!("invalid JSON from {}: {}", url, e)))
+}
+
+/// Resolves an iFlow CLI version spec against the npm registry
+fn resolve_iflow_version(spec: &VersionSpec, registry: &str) -> Result<String> {
+    if let VersionSpec::Exact(version) = spec {
+        return Ok(version.to_string());
+    }
+
+    let url = format!("{}/@iflow-ai%2fiflow-cli", registry.trim_end_matches('/'));
+    let packument = fetch_json(&url)?;
+    let available: Vec<String> = packument["versions"]
+        .as_object()
+        .map(|versions| versions.keys().cloned().collect())
+        .unwrap_or_default();
+    let latest = packument["dist-tags"]["latest"].as_str();
+
+    select_version(spec, &available, latest)
+        .ok_or_else(|| Error::Install(format!("no iFlow CLI version matches '{}'", spec)))
+}
+
+/// Resolves a GitHub CLI version spec against the GitHub releases API
+fn resolve_gh_version(spec: &VersionSpec, api_url: &str) -> Result<String> {
+    if let VersionSpec::Exact(version) = spec {
+        return Ok(version.to_string());
+    }
+
+    let url = format!(
+        "{}/repos/cli/cli/releases?per_page=100",
+        api_url.trim_end_matches('/')
+    );
+    let releases = fetch_json(&url)?;
+    let available: Vec<String> = releases
+        .as_array()
+        .map(|releases| {
+            releases
+                .iter()
+                .filter(|r| !r["draft"].as_bool().unwrap_or(false))
+                .filter(|r| !r["prerelease"].as_bool().unwrap_or(false))
+                .filter_map(|r| r["tag_name"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    select_version(spec, &available, None)
+        .ok_or_else(|| Error::Install(format!("no GitHub CLI version matches '{}'", spec)))
+}
+
+/// Fails fast when the installed iFlow CLI is older than [`MIN_IFLOW_VERSION`].
+/// An undetectable version is reported by [`print_version_info`] and tolerated here.
+pub fn check_iflow_compatibility() -> Result<()> {
+    let Some(installed) = installed_version("iflow") else {
+        return Ok(());
+    };
+    let (Ok(installed_version), Ok(min_version)) = (
+        Version::parse(&installed),
+        Version::parse(MIN_IFLOW_VERSION),
+    ) else {
+        return Ok(());
+    };
+
+    if installed_version < min_version {
+        return Err(Error::Install(format!(
+            "iFlow CLI {} is not supported, version {} or newer is required. Set iflow_version to a newer version",
+            installed, MIN_IFLOW_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Maps the host architecture to the suffix used by GitHub CLI release assets
+pub fn detect_arch() -> Result<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => Ok("amd64"),
+        "aarch64" => Ok("arm64"),
+        other => Err(Error::Install(format!(
+            "unsupported architecture: {}",
+            other
+        ))),
+    }
+}
+
+/// Extracts the version number from `--version` output such as
+/// `gh version 2.76.2 (2025-07-30)` or `0.2.4`
+pub fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|token| token.trim_start_matches('v'))
+        .find(|token| {
+            token.split('.').count() >= 2
+                && token.split(['.', '-', '+']).next().is_some_and(|major| {
+                    !major.is_empty() && major.chars().all(|c| c.is_ascii_digit())
+                })
+        })
+        .map(str::to_string)
+}
+
+/// Finds the SHA-256 checksum of `file_name` in a `checksums.txt` release asset
+pub fn find_checksum(checksums: &str, file_name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let checksum = parts.next()?;
+        (parts.next()? == file_name).then(|| checksum.to_lowercase())
+    })
+}
+
+/// Extracts the version from a GitHub CLI release asset name such as
+/// `gh_2.76.2_linux_amd64.tar.gz` or its top-level directory
+pub fn parse_gh_asset_version(name: &str) -> Option<String> {
+    let name = name.rsplit('/').find(|part| !part.is_empty())?;
+    let version = name.strip_prefix("gh_")?.split('_').next()?;
+    Version::parse(version).ok().map(|v| v.to_string())
+}
+
+/// Reads the GitHub CLI version from a local release tarball
+fn gh_tarball_version(tarball: &Path) -> Result<String> {
+    let listing = run_install_command(
+        Command::new("tar").arg("tzf").arg(tarball),
+        "GitHub CLI tarball listing",
+    )?;
+    String::from_utf8_lossy(&listing)
+        .lines()
+        .next()
+        .and_then(parse_gh_asset_version)
+        .ok_or_else(|| {
+            Error::Install(format!(
+                "{} is not a GitHub CLI release tarball",
+                tarball.display()
+            ))
+        })
+}
+
+/// Reads the iFlow CLI version from a local package tarball
+fn iflow_tarball_version(tarball: &Path) -> Result<String> {
+    let manifest = run_install_command(
+        Command::new("tar")
+            .arg("xzOf")
+            .arg(tarball)
+            .arg("package/package.json"),
+        "iFlow CLI tarball inspection",
+    )?;
+    serde_json::from_slice::<serde_json::Value>(&manifest)
+        .ok()
+        .and_then(|manifest| manifest["version"].as_str().map(str::to_string))
+        .ok_or_else(|| {
+            Error::Install(format!(
+                "{} is not an iFlow CLI package tarball",
+                tarball.display()
+            ))
+        })
+}
+
+/// Returns the installed version of `command`, if it can be determined
+fn installed_version(command: &str) -> Option<String> {
+    get_command_version(command)
+        .ok()
+        .and_then(|output| parse_version(&output))
+}
+
+/// Returns the cache directory, defaulting to the user cache directory
+fn resolve_cache_dir(cache_dir: Option<&str>) -> Result<PathBuf> {
+    match cache_dir {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => dirs::cache_dir()
+            .map(|dir| dir.join("iflow-cli-action"))
+            .ok_or_else(|| Error::Install("failed to determine cache directory".to_string())),
+    }
+}
+
+/// Runs a command and turns a non-zero exit status into an install error
+fn run_install_command(command: &mut Command, what: &str) -> Result<Vec<u8>> {
+    let output = command
+        .output()
+        .map_err(|e| Error::Install(format!("failed to execute {}: {}", what, e)))?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Install(format!("{} failed: {}", what, error.trim())));
+    }
+    Ok(output.stdout)
+}
+
+/// Downloads `url` to `dest`
+fn download(url: &str, dest: &Path) -> Result<()> {
+    run_install_command(
+        Command::new("curl")
+            .arg("-fsSL")
+            .arg("-o")
+            .arg(dest)
+            .arg(url),
+        &format!("download of {}", url),
+    )
+    .map(|_| ())
+}
+
+/// Computes the SHA-256 checksum of a file
+fn sha256_file(path: &Path) -> Result<String> {
+    let stdout =
+        run_install_command(Command::new("sha256sum").arg(path), "sha256sum").or_else(|_| {
+            run_install_command(
+                Command::new("shasum").arg("-a").arg("256").arg(path),
+                "shasum",
+            )
+        })?;
+    String::from_utf8_lossy(&stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_lowercase)
+        .ok_or_else(|| Error::Install(format!("failed to checksum {}", path.display())))
+}
+
+/// Whether a cached `binary` still has the SHA-256 checksum recorded in
+/// `checksum_file` when it was cached
+pub fn verify_cached_binary(binary: &Path, checksum_file: &Path) -> bool {
+    let Ok(expected) = fs::read_to_string(checksum_file) else {
+        return false;
+    };
+    sha256_file(binary).is_ok_and(|actual| actual == expected.trim())
+}
+
+/// Downloads, verifies and caches a GitHub CLI release, then installs it. The
+/// checksum of the cached binary is recorded and checked before it is reused.
+fn install_gh(version: &str, cache_dir: &Path, local_tarball: Option<&Path>) -> Result<()> {
+    let arch = detect_arch()?;
+    let cached_dir = cache_dir.join("gh").join(version).join(arch);
+    let cached_binary = cached_dir.join("gh");
+    let cached_checksum = cached_dir.join("gh.sha256");
+
+    if verify_cached_binary(&cached_binary, &cached_checksum) {
+        logging::info(&format!("Using cached GitHub CLI version: {}", version));
+    } else {
+        if cached_binary.exists() {
+            logging::warn(&format!(
+                "⚠️  Cached GitHub CLI version {} failed verification, installing it again",
+                version
             ));
+            let _ = fs::remove_file(&cached_binary);
         }
+        fs::create_dir_all(&cached_dir)
+            .map_err(|e| Error::Install(format!("failed to create cache directory: {}", e)))?;
 
-        println!(
-            "✅ Successfully installed GitHub CLI version: {}",
-            gh_version
-        );
+        let asset = format!("gh_{}_linux_{}", version, arch);
+        let checksums_name = format!("gh_{}_checksums.txt", version);
+        let (tarball, checksums) = match local_tarball {
+            // Pre-staged artifacts are verified when the checksums file sits next to them
+            Some(local) => (
+                local.to_path_buf(),
+                local
+                    .parent()
+                    .map(|dir| dir.join(&checksums_name))
+                    .filter(|path| path.exists()),
+            ),
+            None => {
+                let release_url =
+                    format!("https://github.com/cli/cli/releases/download/v{}", version);
+                let tarball = cached_dir.join(format!("{}.tar.gz", asset));
+                let checksums = cached_dir.join("checksums.txt");
+                download(&format!("{}/{}.tar.gz", release_url, asset), &tarball)?;
+                download(&format!("{}/{}", release_url, checksums_name), &checksums)?;
+                (tarball, Some(checksums))
+            }
+        };
+
+        // Verify the tarball against the published checksums before using it
+        if let Some(checksums) = checksums {
+            let tarball_name = tarball
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let checksums_content = fs::read_to_string(&checksums)
+                .map_err(|e| Error::Install(format!("failed to read checksums: {}", e)))?;
+            let expected = find_checksum(&checksums_content, &tarball_name).ok_or_else(|| {
+                Error::Install(format!("no checksum published for {}", tarball_name))
+            })?;
+            let actual = sha256_file(&tarball)?;
+            if actual != expected {
+                let _ = fs::remove_dir_all(&cached_dir);
+                return Err(Error::Install(format!(
+                    "checksum mismatch for {}: expected {}, got {}",
+                    tarball_name, expected, actual
+                )));
+            }
+        }
+
+        run_install_command(
+            Command::new("tar")
+                .arg("xzf")
+                .arg(&tarball)
+                .arg("-C")
+                .arg(&cached_dir),
+            "GitHub CLI extraction",
+        )?;
+        fs::rename(
+            cached_dir.join(&asset).join("bin").join("gh"),
+            &cached_binary,
+        )
+        .map_err(|e| Error::Install(format!("failed to cache GitHub CLI binary: {}", e)))?;
+        let _ = fs::remove_dir_all(cached_dir.join(&asset));
+        if local_tarball.is_none() {
+            let _ = fs::remove_file(&tarball);
+        }
+        fs::write(&cached_checksum, sha256_file(&cached_binary)?)
+            .map_err(|e| Error::Install(format!("failed to record GitHub CLI checksum: {}", e)))?;
     }
 
-    // Install specific iFlow CLI version if requested
-    if let Some(iflow_version) = iflow_version
-        && !iflow_version.is_empty()
-    {
-        println!("Installing iFlow CLI version: {}", iflow_version);
+    fs::copy(&cached_binary, Path::new(INSTALL_BIN_DIR).join("gh"))
+        .map_err(|e| Error::Install(format!("failed to install GitHub CLI binary: {}", e)))?;
+    Ok(())
+}
 
-        let output = std::process::Command::new("npm")
-            .arg("install")
+/// Where npm installs iFlow CLI from
+struct NpmSource<'a> {
+    registry: &'a str,
+    /// Local package tarball installed instead of the registry package
+    tarball: Option<&'a Path>,
+    /// Offline cache; when set npm never contacts the registry
+    offline_cache: Option<&'a Path>,
+}
+
+/// Installs an iFlow CLI version into a per-version npm prefix in the cache and
+/// links it into the install directory
+fn install_iflow(version: &str, cache_dir: &Path, source: &NpmSource) -> Result<()> {
+    let prefix = cache_dir.join("iflow").join(version);
+    let cached_binary = prefix.join("bin").join("iflow");
+
+    if cached_binary.exists() {
+        logging::info(&format!("Using cached iFlow CLI version: {}", version));
+    } else {
+        let mut npm = Command::new("npm");
+        npm.arg("install")
             .arg("-g")
-            .arg(format!("@iflow-ai/iflow-cli@{}", iflow_version))
-            .output()
-            .map_err(|e| format!("failed to execute iFlow CLI installation command: {}", e))?;
-
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(format!(
-                "failed to install iFlow CLI version {}: {}",
-                iflow_version, error
-            ));
+            .arg("--prefix")
+            .arg(&prefix)
+            .arg("--registry")
+            .arg(source.registry);
+        if let Some(offline_cache) = source.offline_cache {
+            npm.arg("--offline").arg("--cache").arg(offline_cache);
+        }
+        match source.tarball {
+            Some(tarball) => npm.arg(tarball),
+            None => npm.arg(format!("@iflow-ai/iflow-cli@{}", version)),
+        };
+        run_install_command(
+            &mut npm,
+            &format!("npm install of iFlow CLI version {}", version),
+        )?;
+    }
+
+    let link = Path::new(INSTALL_BIN_DIR).join("iflow");
+    let _ = fs::remove_file(&link);
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&cached_binary, &link)
+        .map_err(|e| Error::Install(format!("failed to link iFlow CLI binary: {}", e)))?;
+    // Without symlinks the launcher is copied
+    #[cfg(not(unix))]
+    fs::copy(&cached_binary, &link)
+        .map_err(|e| Error::Install(format!("failed to copy iFlow CLI binary: {}", e)))?;
+    Ok(())
+}
+
+/// Determines the GitHub CLI version to install, or `None` when it is already installed
+fn target_gh_version(params: &InstallParams) -> Result<Option<String>> {
+    let spec = params
+        .gh_version
+        .filter(|v| !v.is_empty())
+        .map(VersionSpec::parse)
+        .transpose()?;
+    let installed = installed_version("gh");
+
+    let version = if let Some(tarball) = params.gh_tarball.filter(|v| !v.is_empty()) {
+        let version = gh_tarball_version(Path::new(tarball))?;
+        if spec.as_ref().is_some_and(|spec| !spec.matches(&version)) {
+            return Err(Error::Install(format!(
+                "GitHub CLI tarball {} contains version {}, which does not satisfy gh_version '{}'",
+                tarball,
+                version,
+                params.gh_version.unwrap_or_default()
+            )));
+        }
+        version
+    } else if let Some(spec) = spec {
+        match &installed {
+            Some(installed) if spec.matches(installed) => installed.clone(),
+            _ => {
+                let api_url = params.gh_api_url.unwrap_or(DEFAULT_GH_API_URL);
+                let resolved = resolve_gh_version(&spec, api_url)?;
+                if !matches!(spec, VersionSpec::Exact(_)) {
+                    logging::info(&format!(
+                        "Resolved GitHub CLI version {} to {}",
+                        params.gh_version.unwrap_or_default(),
+                        resolved
+                    ));
+                }
+                resolved
+            }
+        }
+    } else {
+        return Ok(None);
+    };
+
+    if installed.as_deref() == Some(version.as_str()) {
+        logging::info(&format!(
+            "GitHub CLI version {} is already installed",
+            version
+        ));
+        return Ok(None);
+    }
+    Ok(Some(version))
+}
+
+/// Determines the iFlow CLI version to install, or `None` when it is already installed
+fn target_iflow_version(params: &InstallParams) -> Result<Option<String>> {
+    let spec = params
+        .iflow_version
+        .filter(|v| !v.is_empty())
+        .map(VersionSpec::parse)
+        .transpose()?;
+    let installed = installed_version("iflow");
+
+    let version = if let Some(tarball) = params.iflow_tarball.filter(|v| !v.is_empty()) {
+        let version = iflow_tarball_version(Path::new(tarball))?;
+        if spec.as_ref().is_some_and(|spec| !spec.matches(&version)) {
+            return Err(Error::Install(format!(
+                "iFlow CLI tarball {} contains version {}, which does not satisfy iflow_version '{}'",
+                tarball,
+                version,
+                params.iflow_version.unwrap_or_default()
+            )));
+        }
+        version
+    } else if let Some(spec) = spec {
+        match &installed {
+            Some(installed) if spec.matches(installed) => installed.clone(),
+            _ => {
+                let registry = params.npm_registry.unwrap_or(DEFAULT_NPM_REGISTRY);
+                let resolved = resolve_iflow_version(&spec, registry)?;
+                if !matches!(spec, VersionSpec::Exact(_)) {
+                    logging::info(&format!(
+                        "Resolved iFlow CLI version {} to {}",
+                        params.iflow_version.unwrap_or_default(),
+                        resolved
+                    ));
+                }
+                resolved
+            }
         }
+    } else {
+        return Ok(None);
+    };
+
+    if installed.as_deref() == Some(version.as_str()) {
+        logging::info(&format!(
+            "iFlow CLI version {} is already installed",
+            version
+        ));
+        return Ok(None);
+    }
+    Ok(Some(version))
+}
 
-        println!(
+/// Installs specific versions of GitHub CLI and iFlow CLI if requested.
+/// Versions may be exact, semver ranges or `latest`; ranges and `latest` are
+/// resolved against the configured registries unless the installed version
+/// already satisfies them. Local tarballs and an offline npm cache allow
+/// installing on runners without internet access. Versions that are already
+/// installed are skipped, and installs are cached per version so repeated
+/// runs on self-hosted runners are near-instant.
+pub fn install_specific_versions(params: &InstallParams) -> Result<()> {
+    let cache_dir = resolve_cache_dir(params.cache_dir)?;
+
+    // Install specific GitHub CLI version if requested
+    if let Some(gh_version) = target_gh_version(params)? {
+        logging::info(&format!("Installing GitHub CLI version: {}", gh_version));
+        let local_tarball = params.gh_tarball.filter(|v| !v.is_empty()).map(Path::new);
+        install_gh(&gh_version, &cache_dir, local_tarball)?;
+        logging::info(&format!(
+            "✅ Successfully installed GitHub CLI version: {}",
+            gh_version
+        ));
+    }
+
+    // Install specific iFlow CLI version if requested
+    if let Some(iflow_version) = target_iflow_version(params)? {
+        logging::info(&format!("Installing iFlow CLI version: {}", iflow_version));
+        let source = NpmSource {
+            registry: params.npm_registry.unwrap_or(DEFAULT_NPM_REGISTRY),
+            tarball: params
+                .iflow_tarball
+                .filter(|v| !v.is_empty())
+                .map(Path::new),
+            offline_cache: params.npm_cache.filter(|v| !v.is_empty()).map(Path::new),
+        };
+        install_iflow(&iflow_version, &cache_dir, &source)?;
+        logging::info(&format!(
             "✅ Successfully installed iFlow CLI version: {}",
             iflow_version
-        );
+        ));
     }
 
     Ok(())
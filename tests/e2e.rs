@@ -208,6 +208,141 @@ fn test_validation_error_missing_api_key_and_settings_json() {
     assert!(stderr.contains("Validation Error: api_key input is required and cannot be empty"));
 }
 
+#[test]
+fn test_configuration_with_openai_compatible_auth() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "provider-api-key",
+            "--auth-type",
+            "openai-compatible",
+            "--base-url",
+            "https://llm.example.com/v1",
+            "--search-api-key",
+            "search-api-key",
+            "--max-turns",
+            "20",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // Check that the command succeeded
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Check the content of the settings file
+    let content = fs::read_to_string(&settings_file).expect("Failed to read settings file");
+    assert!(content.contains("\"selectedAuthType\": \"openai-compatible\""));
+    assert!(content.contains("\"apiKey\": \"provider-api-key\""));
+    assert!(content.contains("\"searchApiKey\": \"search-api-key\""));
+    assert!(content.contains("\"baseUrl\": \"https://llm.example.com/v1\""));
+    assert!(content.contains("\"maxSessionTurns\": 20"));
+}
+
+#[test]
+fn test_validation_error_openai_compatible_requires_base_url() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "provider-api-key",
+            "--auth-type",
+            "openai-compatible",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // Check that the command failed
+    assert!(!output.status.success());
+
+    // Check the error message
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(
+        "Validation Error: base_url must point to your OpenAI-compatible endpoint when auth_type is 'openai-compatible'"
+    ));
+}
+
+#[test]
+fn test_fallback_models_parsed() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .env_remove("GITHUB_ACTIONS")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--fallback-models",
+            "kimi-k2, qwen3-coder-plus\nglm-4.5",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // Check that the command succeeded
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The primary model is not repeated in the fallback chain
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("DRY RUN: Models: qwen3-coder-plus, kimi-k2, glm-4.5"));
+}
+
 #[test]
 fn test_validation_error_invalid_settings_json() {
     // Create a temporary directory for testing
@@ -247,6 +382,144 @@ fn test_validation_error_invalid_settings_json() {
     assert!(stderr.contains("Validation Error: invalid settings_json provided"));
 }
 
+#[test]
+fn test_validation_error_invalid_output_schema() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--output-schema",
+            r#"{"type": "no-such-type"}"#,
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Validation Error: invalid output_schema"));
+}
+
+#[test]
+fn test_validation_error_triage_requires_github_token() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    // No prompt is needed in triage mode, but a token is
+    let output = Command::new("cargo")
+        .env_remove("INPUT_GITHUB_TOKEN")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--api-key",
+            "test-api-key",
+            "--triage",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Validation Error: triage requires a github_token"));
+}
+
+#[test]
+fn test_validation_error_idle_timeout_above_timeout() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--timeout",
+            "60",
+            "--idle-timeout",
+            "120",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("idle_timeout value is out of range"));
+}
+
+#[test]
+fn test_validation_error_fail_on_stop_reason_not_supported() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--fail-on-stop-reason",
+            "max-tokens",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // iFlow never reports a stop reason that could fail the step
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("fail_on_stop_reason is not supported"));
+}
+
 #[test]
 fn test_precmd_execution() {
     // Create a temporary directory for testing
@@ -404,10 +677,13 @@ fn test_precmd_execution_fails() {
     assert!(
         stderr.contains("Pre-command Error: pre-command 'exit 1' failed with exit code: Some(1)")
     );
+
+    // Pre-command failures have their own exit code
+    assert_eq!(output.status.code(), Some(5));
 }
 
 #[test]
-fn test_dry_run_mode() {
+fn test_error_kind_output_written() {
     // Create a temporary directory for testing
     let temp_dir = tempfile::Builder::new()
         .prefix("iflow_cli_test")
@@ -416,16 +692,16 @@ fn test_dry_run_mode() {
 
     let temp_path = temp_dir.path();
     let settings_file = temp_path.join("settings.json");
+    let github_output = temp_path.join("github_output.txt");
 
     let output = Command::new("cargo")
-        .env("GITHUB_ACTIONS", "true") // Enable GitHub Actions mode to force WebSocket usage
+        .env("GITHUB_ACTIONS", "true")
+        .env("GITHUB_OUTPUT", &github_output)
         .args([
             "run",
             "--bin",
             "iflow-cli-action",
             "--",
-            "--prompt",
-            "test prompt",
             "--api-key",
             "test-api-key",
             "--dry-run",
@@ -435,23 +711,25 @@ fn test_dry_run_mode() {
         .output()
         .expect("Failed to execute test");
 
-    // Check that the command succeeded
+    // Validation failures exit with code 2
+    assert_eq!(output.status.code(), Some(2));
+
+    // The failure category is exposed for workflows to branch on
+    let content = std::fs::read_to_string(&github_output).expect("Failed to read GITHUB_OUTPUT");
     assert!(
-        output.status.success(),
-        "Command failed with stderr: {}",
-        String::from_utf8_lossy(&output.stderr)
+        content.contains("exit_code=2"),
+        "GITHUB_OUTPUT: {}",
+        content
+    );
+    assert!(
+        content.contains("error_kind=validation"),
+        "GITHUB_OUTPUT: {}",
+        content
     );
-
-    // Check that settings file was created
-    assert!(settings_file.exists(), "Settings file was not created");
-
-    // Check that dry run message was printed
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("DRY RUN: Would execute communicate_with_iflow_cli_via_acp()"));
 }
 
 #[test]
-fn test_github_actions_outputs_written() {
+fn test_install_skipped_when_version_present() {
     // Create a temporary directory for testing
     let temp_dir = tempfile::Builder::new()
         .prefix("iflow_cli_test")
@@ -461,14 +739,28 @@ fn test_github_actions_outputs_written() {
     let temp_path = temp_dir.path();
     let settings_file = temp_path.join("settings.json");
 
-    // Temp files for GITHUB_OUTPUT and GITHUB_STEP_SUMMARY
-    let github_output = temp_path.join("github_output.txt");
-    let github_summary = temp_path.join("github_summary.md");
+    // Fake gh binary reporting the requested version
+    let bin_dir = temp_path.join("bin");
+    fs::create_dir_all(&bin_dir).expect("Failed to create bin directory");
+    let fake_gh = bin_dir.join("gh");
+    fs::write(
+        &fake_gh,
+        "#!/bin/sh\necho 'gh version 9.9.9 (2025-01-01)'\n",
+    )
+    .expect("Failed to write fake gh");
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&fake_gh, fs::Permissions::from_mode(0o755))
+            .expect("Failed to make fake gh executable");
+    }
+    let path = format!(
+        "{}:{}",
+        bin_dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
 
     let output = Command::new("cargo")
-        .env("GITHUB_ACTIONS", "true")
-        .env("GITHUB_OUTPUT", &github_output)
-        .env("GITHUB_STEP_SUMMARY", &github_summary)
+        .env("PATH", path)
         .args([
             "run",
             "--bin",
@@ -478,6 +770,10 @@ fn test_github_actions_outputs_written() {
             "test prompt",
             "--api-key",
             "test-api-key",
+            "--gh-version",
+            "9.9.9",
+            "--cache-dir",
+            temp_path.join("cache").to_str().unwrap(),
             "--dry-run",
             "--settings-file-path",
             settings_file.to_str().unwrap(),
@@ -485,27 +781,679 @@ fn test_github_actions_outputs_written() {
         .output()
         .expect("Failed to execute test");
 
-    // Command should succeed
+    // Check that the command succeeded without downloading anything
     assert!(
         output.status.success(),
-        "Command failed: stderr: {}",
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("GitHub CLI version 9.9.9 is already installed"));
+    assert!(!temp_path.join("cache").join("gh").exists());
+}
+
+/// Writes an executable script reporting `version` into `bin_dir`
+fn write_fake_tool(bin_dir: &std::path::Path, name: &str, version_output: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::create_dir_all(bin_dir).expect("Failed to create bin directory");
+    let tool = bin_dir.join(name);
+    fs::write(&tool, format!("#!/bin/sh\necho '{}'\n", version_output))
+        .expect("Failed to write fake tool");
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755))
+        .expect("Failed to make fake tool executable");
+}
+
+/// Starts a stub npm registry that serves the iFlow CLI packument once
+fn serve_packument() -> String {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let registry = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let body = r#"{"dist-tags":{"latest":"9.2.0"},"versions":{"9.1.0":{},"9.2.0":{}}}"#;
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    registry
+}
+
+#[test]
+fn test_iflow_version_without_match_reports_spec() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+    let temp_path = temp_dir.path();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--iflow-version",
+            "^9.5",
+            "--npm-registry",
+            &serve_packument(),
+            "--cache-dir",
+            temp_path.join("cache").to_str().unwrap(),
+            "--dry-run",
+            "--settings-file-path",
+            temp_path.join("settings.json").to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // The spec is reported as the user wrote it
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("no iFlow CLI version matches '^9.5'"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_iflow_version_latest_resolved_from_registry() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let bin_dir = temp_path.join("bin");
+    write_fake_tool(&bin_dir, "iflow", "9.2.0");
+
+    let registry = serve_packument();
+
+    let path = format!(
+        "{}:{}",
+        bin_dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let output = Command::new("cargo")
+        .env("PATH", path)
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--iflow-version",
+            "latest",
+            "--npm-registry",
+            &registry,
+            "--cache-dir",
+            temp_path.join("cache").to_str().unwrap(),
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Resolved iFlow CLI version latest to 9.2.0"));
+    assert!(stdout.contains("iFlow CLI version 9.2.0 is already installed"));
+}
+
+/// Writes an `npm pack` style iFlow CLI tarball of `version` into `dir`
+fn write_iflow_tarball(dir: &std::path::Path, version: &str) -> std::path::PathBuf {
+    let package_dir = dir.join("package");
+    fs::create_dir_all(&package_dir).expect("Failed to create package directory");
+    fs::write(
+        package_dir.join("package.json"),
+        format!(
+            r#"{{"name":"@iflow-ai/iflow-cli","version":"{}"}}"#,
+            version
+        ),
+    )
+    .expect("Failed to write package.json");
+    let tarball = dir.join(format!("iflow-ai-iflow-cli-{}.tgz", version));
+    let status = Command::new("tar")
+        .arg("czf")
+        .arg(&tarball)
+        .arg("-C")
+        .arg(dir)
+        .arg("package")
+        .status()
+        .expect("Failed to create tarball");
+    assert!(status.success());
+    tarball
+}
+
+#[test]
+fn test_iflow_tarball_version_mismatch_reports_spec() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+    let temp_path = temp_dir.path();
+    let tarball = write_iflow_tarball(temp_path, "9.3.0");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--iflow-version",
+            ">=9.5, <10",
+            "--iflow-tarball",
+            tarball.to_str().unwrap(),
+            "--cache-dir",
+            temp_path.join("cache").to_str().unwrap(),
+            "--dry-run",
+            "--settings-file-path",
+            temp_path.join("settings.json").to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // The spec is reported as the user wrote it
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr
+            .contains("contains version 9.3.0, which does not satisfy iflow_version '>=9.5, <10'"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_iflow_tarball_version_used_offline() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let bin_dir = temp_path.join("bin");
+    write_fake_tool(&bin_dir, "iflow", "9.3.0");
+
+    let tarball = write_iflow_tarball(temp_path, "9.3.0");
+
+    let path = format!(
+        "{}:{}",
+        bin_dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let output = Command::new("cargo")
+        .env("PATH", path)
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--iflow-version",
+            "^9.3",
+            "--iflow-tarball",
+            tarball.to_str().unwrap(),
+            "--npm-registry",
+            "http://127.0.0.1:9",
+            "--cache-dir",
+            temp_path.join("cache").to_str().unwrap(),
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // The version comes from the tarball, so the unreachable registry is never used
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("iFlow CLI version 9.3.0 is already installed"));
+    assert!(stdout.contains("iFlow CLI version: 9.3.0"));
+}
+
+#[test]
+fn test_unsupported_iflow_version_fails_fast() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let bin_dir = temp_path.join("bin");
+    write_fake_tool(&bin_dir, "iflow", "0.1.0");
+
+    let path = format!(
+        "{}:{}",
+        bin_dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let output = Command::new("cargo")
+        .env("PATH", path)
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // Too-old iFlow CLI is reported as an installation error before connecting
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("iFlow CLI 0.1.0 is not supported"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_traces_exported_to_otlp_collector() {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    // Stub OTLP/HTTP collector collecting the request paths and bodies
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            let _ = reader.read_line(&mut request_line);
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).unwrap_or(0) == 0 || header == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            let _ = reader.read_exact(&mut body);
+            let _ = reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            let _ = tx.send((request_line, body));
+        }
+    });
+
+    let output = Command::new("cargo")
+        .env_remove("GITHUB_ACTIONS")
+        .env("GITHUB_REPOSITORY", "iflow-ai/iflow-cli-action")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--otlp-endpoint",
+            &endpoint,
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Spans are flushed before the process exits
+    let (request_line, body) = rx
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .expect("no traces exported");
+    assert!(
+        request_line.starts_with("POST /v1/traces"),
+        "{}",
+        request_line
+    );
+    let body = String::from_utf8_lossy(&body);
+    for expected in ["run", "validate", "install", "configure", "precmd"] {
+        assert!(body.contains(expected), "span {} missing", expected);
+    }
+    assert!(body.contains("iflow-ai/iflow-cli-action"));
+
+    // iFlow CLI telemetry points at the same collector
+    let content = fs::read_to_string(&settings_file).expect("Failed to read settings file");
+    assert!(content.contains(&format!("\"otlpEndpoint\": \"{}\"", endpoint)));
+    assert!(content.contains("\"logPrompts\": false"));
+}
+
+#[test]
+fn test_dry_run_mode() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .env("GITHUB_ACTIONS", "true") // Enable GitHub Actions mode to force WebSocket usage
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // Check that the command succeeded
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Check that settings file was created
+    assert!(settings_file.exists(), "Settings file was not created");
+
+    // Check that dry run message was printed
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("DRY RUN: Would execute communicate_with_iflow_cli_via_acp()"));
+}
+
+#[test]
+fn test_json_log_format() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .env("GITHUB_ACTIONS", "true")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--precmd",
+            "echo precmd-ran",
+            "--log-format",
+            "json",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+            "--working-directory",
+            temp_path.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Every stdout line is a JSON object; workflow commands go to stderr
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("::add-mask::test-api-key"),
+        "stderr: {}",
+        stderr
+    );
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| {
+            serde_json::from_str(line).unwrap_or_else(|e| panic!("not JSON: {} ({})", line, e))
+        })
+        .collect();
+
+    assert!(events.iter().all(|e| e["timestamp"].is_string()));
+    assert!(
+        events
+            .iter()
+            .any(|e| e["event"] == "precmd_output" && e["message"] == "precmd-ran\n")
+    );
+    assert!(
+        events
+            .iter()
+            .any(|e| e["message"] == "DRY RUN: Would execute communicate_with_iflow_cli_via_acp()")
+    );
+}
+
+#[test]
+fn test_github_actions_outputs_written() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    // Temp files for GITHUB_OUTPUT and GITHUB_STEP_SUMMARY
+    let github_output = temp_path.join("github_output.txt");
+    let github_summary = temp_path.join("github_summary.md");
+
+    let output = Command::new("cargo")
+        .env("GITHUB_ACTIONS", "true")
+        .env("GITHUB_OUTPUT", &github_output)
+        .env("GITHUB_STEP_SUMMARY", &github_summary)
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    // Command should succeed
+    assert!(
+        output.status.success(),
+        "Command failed: stderr: {}",
         String::from_utf8_lossy(&output.stderr)
     );
 
     // Read GITHUB_OUTPUT file
     let content = std::fs::read_to_string(&github_output).expect("Failed to read GITHUB_OUTPUT");
 
-    // Should contain exit_code and result keys
-    assert!(
-        content.contains("exit_code=0") || content.contains("exit_code<<EOF"),
-        "GITHUB_OUTPUT missing exit_code: {}",
-        content
-    );
+    // Should contain exit_code and result keys
+    assert!(
+        content.contains("exit_code=0") || content.contains("exit_code<<EOF"),
+        "GITHUB_OUTPUT missing exit_code: {}",
+        content
+    );
+    assert!(
+        content.contains("result=") || content.contains("result<<EOF"),
+        "GITHUB_OUTPUT missing result: {}",
+        content
+    );
+    assert!(
+        content.contains("model_used=qwen3-coder-plus"),
+        "GITHUB_OUTPUT missing model_used: {}",
+        content
+    );
+
+    assert!(
+        content.contains("duration_ms="),
+        "GITHUB_OUTPUT missing duration_ms: {}",
+        content
+    );
+
+    // Environment report is a single-line JSON object
+    let environment = content
+        .lines()
+        .find_map(|line| line.strip_prefix("environment="))
+        .expect("GITHUB_OUTPUT missing environment");
+    let environment: serde_json::Value =
+        serde_json::from_str(environment).expect("environment output is not JSON");
+    assert_eq!(environment["model"], "qwen3-coder-plus");
+    assert_eq!(environment["base_url_host"], "apis.iflow.cn");
+    assert_eq!(environment["os"], std::env::consts::OS);
+}
+
+#[test]
+fn test_api_key_not_printed_in_cli_mode() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .env_remove("GITHUB_ACTIONS")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "super-secret-api-key",
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The arguments are no longer dumped, and the API key never appears
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Parsed arguments"));
+    assert!(!stdout.contains("super-secret-api-key"));
+}
+
+#[test]
+fn test_secrets_masked_in_github_actions() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+
+    let output = Command::new("cargo")
+        .env("GITHUB_ACTIONS", "true")
+        .env_remove("GITHUB_OUTPUT")
+        .args([
+            "run",
+            "--bin",
+            "iflow-cli-action",
+            "--",
+            "--prompt",
+            "test prompt",
+            "--settings-json",
+            r#"{"apiKey":"masked-api-key","searchApiKey":"masked-search-key"}"#,
+            "--dry-run",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
     assert!(
-        content.contains("result=") || content.contains("result<<EOF"),
-        "GITHUB_OUTPUT missing result: {}",
-        content
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
     );
+
+    // Every secret in settings_json is registered with the runner
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("::add-mask::masked-api-key"));
+    assert!(stdout.contains("::add-mask::masked-search-key"));
 }
 
 // This test only runs on GitHub Actions. Locally it will be skipped.
@@ -574,3 +1522,463 @@ fn test_only_on_github_actions() {
         status.code()
     );
 }
+
+#[test]
+fn test_sigterm_cancels_run() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let github_output = temp_path.join("github_output.txt");
+    let step_summary = temp_path.join("step_summary.md");
+    std::fs::write(&settings_file, "{\"theme\": \"Dark\"}").unwrap();
+
+    // Run the binary directly so the signal reaches it rather than cargo
+    let child = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .env("GITHUB_ACTIONS", "true")
+        .env("GITHUB_OUTPUT", &github_output)
+        .env("GITHUB_STEP_SUMMARY", &step_summary)
+        .args([
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--precmd",
+            "sleep 30",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute test");
+
+    // Wait until the pre-command runs, i.e. the settings were written
+    let started = std::time::Instant::now();
+    while std::fs::read_to_string(&settings_file).unwrap_or_default() == "{\"theme\": \"Dark\"}" {
+        assert!(started.elapsed().as_secs() < 60, "settings never written");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .expect("Failed to send SIGTERM");
+
+    let output = child.wait_with_output().expect("Failed to wait for run");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(130), "stderr: {}", stderr);
+    assert!(
+        stderr.contains("Cancelled: received SIGTERM"),
+        "stderr: {}",
+        stderr
+    );
+
+    // Outputs and the summary report the cancellation
+    let content = std::fs::read_to_string(&github_output).expect("Failed to read GITHUB_OUTPUT");
+    assert!(
+        content.contains("exit_code=130"),
+        "GITHUB_OUTPUT: {}",
+        content
+    );
+    assert!(
+        content.contains("error_kind=cancelled"),
+        "GITHUB_OUTPUT: {}",
+        content
+    );
+    let summary = std::fs::read_to_string(&step_summary).expect("Failed to read step summary");
+    assert!(summary.contains("## 🚫 iFlow CLI Execution Summary - Cancelled"));
+
+    // The settings file the run overwrote is put back
+    assert_eq!(
+        std::fs::read_to_string(&settings_file).unwrap(),
+        "{\"theme\": \"Dark\"}"
+    );
+}
+
+#[test]
+fn test_summary_written_when_every_attempt_fails_to_connect() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let step_summary = temp_path.join("step_summary.md");
+
+    // Without iFlow on the PATH every attempt fails to start it
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .env("PATH", "/usr/bin:/bin")
+        .env("GITHUB_ACTIONS", "true")
+        .env("GITHUB_STEP_SUMMARY", &step_summary)
+        .args([
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--max-retries",
+            "1",
+            "--retry-initial-delay",
+            "0",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(6), "stderr: {}", stderr);
+
+    // The summary reports the failure and both attempts
+    let summary = std::fs::read_to_string(&step_summary).expect("Failed to read step summary");
+    assert!(
+        summary.contains("## ❌ iFlow CLI Execution Summary"),
+        "summary: {}",
+        summary
+    );
+    assert!(
+        summary.contains("💥 **Exit Code**: 6"),
+        "summary: {}",
+        summary
+    );
+    assert!(
+        summary.contains("| retried after 0.0s |"),
+        "summary: {}",
+        summary
+    );
+    assert!(
+        summary.contains("| retries exhausted |"),
+        "summary: {}",
+        summary
+    );
+}
+
+#[test]
+fn test_run_loads_workflow_step_inputs() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let workflow = temp_path.join("review.yml");
+    fs::write(
+        &workflow,
+        r#"on: pull_request
+jobs:
+  review:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - id: review
+        uses: iflow-ai/iflow-cli-action@v2
+        with:
+          prompt: "Review ${{ github.event.pull_request.title }}"
+          api_key: ${{ secrets.IFLOW_API_KEY }}
+          model: kimi-k2
+          fallback_models: glm-4.5
+          dry_run: true
+"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .env_remove("GITHUB_ACTIONS")
+        .env("IFLOW_API_KEY", "workflow-secret-key")
+        .args([
+            "run",
+            "--workflow",
+            workflow.to_str().unwrap(),
+            "--prompt",
+            "local prompt",
+            "--fallback-models",
+            "qwen3-coder-plus",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Inputs come from the step, secrets from the environment, and flags win
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Loaded 4 input(s) from workflow step 'review'"));
+    assert!(stdout.contains("DRY RUN: Models: kimi-k2, qwen3-coder-plus"));
+    assert!(!stdout.contains("workflow-secret-key"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipped input prompt"));
+    let content = fs::read_to_string(&settings_file).expect("Failed to read settings file");
+    assert!(content.contains("\"apiKey\": \"workflow-secret-key\""));
+}
+
+#[test]
+fn test_run_writes_summary_file() {
+    // Create a temporary directory for testing
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+
+    let temp_path = temp_dir.path();
+    let settings_file = temp_path.join("settings.json");
+    let summary_file = temp_path.join("summary.md");
+
+    // A cancelled local run still writes its summary
+    let child = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .env_remove("GITHUB_ACTIONS")
+        .args([
+            "run",
+            "--summary",
+            summary_file.to_str().unwrap(),
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--precmd",
+            "sleep 30",
+            "--settings-file-path",
+            settings_file.to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute test");
+
+    // Wait until the pre-command runs, i.e. the settings were written
+    let started = std::time::Instant::now();
+    while !settings_file.exists() {
+        assert!(started.elapsed().as_secs() < 60, "settings never written");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .expect("Failed to send SIGTERM");
+
+    let output = child.wait_with_output().expect("Failed to wait for run");
+    assert_eq!(
+        output.status.code(),
+        Some(130),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let summary = fs::read_to_string(&summary_file).expect("Failed to read summary file");
+    assert!(summary.contains("## 🚫 iFlow CLI Execution Summary - Cancelled"));
+}
+
+#[test]
+fn test_validate_reports_invalid_inputs() {
+    // Validation errors exit with their own code without running anything
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "validate",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+            "--auth-type",
+            "openai-compatible",
+            "--base-url",
+            "",
+        ])
+        .output()
+        .expect("Failed to execute test");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "validate",
+            "--prompt",
+            "test prompt",
+            "--api-key",
+            "test-api-key",
+        ])
+        .output()
+        .expect("Failed to execute test");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Inputs and settings are valid"));
+}
+
+#[test]
+fn test_print_settings_redacts_secrets() {
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "print-settings",
+            "--api-key",
+            "sk-print-settings-key",
+            "--model",
+            "kimi-k2",
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let settings: serde_json::Value = serde_json::from_str(&stdout).expect("settings are JSON");
+    assert_eq!(settings["apiKey"], "***");
+    assert_eq!(settings["modelName"], "kimi-k2");
+    assert!(!stdout.contains("sk-print-settings-key"));
+}
+
+#[test]
+fn test_summarize_renders_saved_log() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+    let log_file = temp_dir.path().join("run.log");
+    let summary_file = temp_dir.path().join("summary.md");
+    fs::write(
+        &log_file,
+        concat!(
+            r#"{"event":"assistant","message":"Reviewed the change"}"#,
+            "\n",
+            r#"{"event":"failure","message":"Timeout Error: timed out","kind":"timeout","exitCode":124}"#,
+            "\n",
+        ),
+    )
+    .expect("Failed to write log");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "summarize",
+            log_file.to_str().unwrap(),
+            "--summary",
+            summary_file.to_str().unwrap(),
+            "--model",
+            "kimi-k2",
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let summary = fs::read_to_string(&summary_file).expect("Failed to read summary file");
+    assert!(summary.contains("Timeout"));
+    assert!(summary.contains("`kimi-k2`"));
+    assert!(summary.contains("Reviewed the change"));
+}
+
+#[test]
+fn test_summarize_without_flags_omits_settings() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+    let log_file = temp_dir.path().join("run.log");
+    let summary_file = temp_dir.path().join("summary.md");
+    fs::write(
+        &log_file,
+        r#"{"event":"assistant","message":"Reviewed the change"}"#,
+    )
+    .expect("Failed to write log");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args([
+            "summarize",
+            log_file.to_str().unwrap(),
+            "--summary",
+            summary_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // The log does not record the settings, so the defaults are not claimed
+    let summary = fs::read_to_string(&summary_file).expect("Failed to read summary file");
+    assert!(summary.contains("Reviewed the change"));
+    assert!(
+        !summary.contains("### ⚙️ Configuration"),
+        "summary: {}",
+        summary
+    );
+    assert!(
+        !summary.contains("qwen3-coder-plus"),
+        "summary: {}",
+        summary
+    );
+}
+
+#[test]
+fn test_config_file_profile_inputs() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("iflow_cli_test")
+        .tempdir()
+        .expect("Failed to create temporary directory");
+    let config_file = temp_dir.path().join("iflow.yml");
+    let settings_file = temp_dir.path().join("settings.json");
+    fs::write(
+        &config_file,
+        r#"
+defaults:
+  model: kimi-k2
+  fallback_models: [glm-4.5]
+profiles:
+  review:
+    model: deepseek-v3
+    prompt: Review the pull request
+"#,
+    )
+    .expect("Failed to write config file");
+
+    // The workflow's inputs override the profile, which overrides the
+    // defaults; empty inputs count as not given
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .env("INPUT_CONFIG_FILE", &config_file)
+        .env("INPUT_PROFILE", "review")
+        .env("INPUT_API_KEY", "test-api-key")
+        .env("INPUT_FALLBACK_MODELS", "qwen3-coder-plus")
+        .env("INPUT_MODEL", "")
+        .env("INPUT_IDLE_TIMEOUT", "")
+        .env("INPUT_DRY_RUN", "true")
+        .env("SETTINGS_FILE_PATH", &settings_file)
+        .output()
+        .expect("Failed to execute test");
+
+    assert!(
+        output.status.success(),
+        "Command failed with stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Loaded 3 input(s) from config file"));
+    assert!(stdout.contains("(profile 'review')"));
+    assert!(stdout.contains("DRY RUN: Models: deepseek-v3, qwen3-coder-plus"));
+
+    // A profile the file does not define is a validation error
+    let output = Command::new(env!("CARGO_BIN_EXE_iflow-cli-action"))
+        .args(["validate", "--api-key", "test-api-key", "--profile", "docs"])
+        .arg("--config-file")
+        .arg(&config_file)
+        .output()
+        .expect("Failed to execute test");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("no profile 'docs', available: review")
+    );
+}